@@ -0,0 +1,22 @@
+-- Tables as they existed before migrations were tracked. `if not exists`
+-- lets a database created by hand adopt the migration history.
+create table if not exists restaurant (
+    id varchar(36) not null,
+    name varchar(255) not null,
+    address varchar(255) not null,
+    x double not null,
+    y double not null,
+    kakao_place_id varchar(32) not null,
+    api_called_at datetime not null,
+    scraped_at datetime null,
+    created_at datetime not null,
+    updated_at datetime null,
+    primary key (id)
+);
+
+create table if not exists restaurant_categories (
+    restaurant_id varchar(36) not null,
+    categories varchar(64) not null,
+    primary key (restaurant_id, categories),
+    foreign key (restaurant_id) references restaurant (id) on delete cascade
+);
@@ -0,0 +1,4 @@
+alter table restaurant
+    add column road_address varchar(255) null after address,
+    add column phone varchar(32) null after road_address,
+    add column place_url varchar(255) null after phone;
@@ -0,0 +1,4 @@
+alter table restaurant
+    add column missing_since datetime null,
+    add column miss_count int not null default 0,
+    add column deleted_at datetime null;
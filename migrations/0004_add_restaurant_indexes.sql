@@ -0,0 +1,72 @@
+-- Upserts match rows by kakao_place_id, and area queries filter on x and y.
+--
+-- Syncs before upserts inserted a new row on every run, so older databases
+-- hold a place several times. The oldest row of each place is kept and the
+-- categories of the others are moved onto it before the unique index is added.
+
+-- Tables made by hand before 0001 may lack the key category upserts match
+-- on. Rebuild the rows without duplicates and add it where it is missing.
+create temporary table restaurant_categories_distinct as
+select distinct restaurant_id, categories from restaurant_categories;
+
+delete from restaurant_categories;
+
+insert into restaurant_categories (restaurant_id, categories)
+select restaurant_id, categories from restaurant_categories_distinct;
+
+drop temporary table restaurant_categories_distinct;
+
+set @add_key = (
+    select if(count(*) = 0,
+        'alter table restaurant_categories add primary key (restaurant_id, categories)',
+        'do 0')
+    from information_schema.table_constraints
+    where table_schema = database()
+        and table_name = 'restaurant_categories'
+        and constraint_type = 'PRIMARY KEY'
+);
+prepare add_key from @add_key;
+execute add_key;
+deallocate prepare add_key;
+
+create temporary table restaurant_keep (
+    kakao_place_id varchar(32) not null,
+    keep_id varchar(36) not null,
+    primary key (kakao_place_id)
+);
+
+insert into restaurant_keep (kakao_place_id, keep_id)
+select r.kakao_place_id, min(r.id)
+from restaurant r
+join (
+    select kakao_place_id, min(created_at) as created_at
+    from restaurant
+    group by kakao_place_id
+) f on f.kakao_place_id = r.kakao_place_id and f.created_at = r.created_at
+group by r.kakao_place_id;
+
+insert ignore into restaurant_categories (restaurant_id, categories)
+select k.keep_id, c.categories
+from restaurant_categories c
+join restaurant r on r.id = c.restaurant_id
+join restaurant_keep k on k.kakao_place_id = r.kakao_place_id
+where r.id <> k.keep_id;
+
+delete c from restaurant_categories c
+join restaurant r on r.id = c.restaurant_id
+join restaurant_keep k on k.kakao_place_id = r.kakao_place_id
+where r.id <> k.keep_id;
+
+delete r from restaurant r
+join restaurant_keep k on k.kakao_place_id = r.kakao_place_id
+where r.id <> k.keep_id;
+
+-- Hand-made tables have no foreign key to clean up after deleted restaurants.
+delete c from restaurant_categories c
+left join restaurant r on r.id = c.restaurant_id
+where r.id is null;
+
+drop temporary table restaurant_keep;
+
+create unique index restaurant_kakao_place_id on restaurant (kakao_place_id);
+create index restaurant_x_y on restaurant (x, y);
@@ -0,0 +1,27 @@
+-- Left null for existing rows, which came from either FD6 or CE7. The next
+-- sync of their area fills it in.
+alter table restaurant
+    add column category_group_code varchar(3) null after kakao_place_id;
+
+create table place (
+    id varchar(36) not null,
+    name varchar(255) not null,
+    address varchar(255) not null,
+    road_address varchar(255) null,
+    phone varchar(32) null,
+    place_url varchar(255) null,
+    x double not null,
+    y double not null,
+    kakao_place_id varchar(32) not null,
+    category_group_code varchar(3) not null,
+    category_name varchar(255) not null,
+    api_called_at datetime not null,
+    created_at datetime not null,
+    updated_at datetime null,
+    missing_since datetime null,
+    miss_count int not null default 0,
+    deleted_at datetime null,
+    primary key (id),
+    unique index place_kakao_place_id (kakao_place_id),
+    index place_group_x_y (category_group_code, x, y)
+);
@@ -0,0 +1,6 @@
+-- Left null for existing rows until the next sync of their area.
+alter table restaurant
+    add column category_name varchar(255) null after category_group_code,
+    add column primary_category varchar(64) null after category_name;
+
+create index restaurant_primary_category on restaurant (primary_category);
@@ -0,0 +1,4 @@
+-- Rows from before names were used all came from the Kakao category path.
+alter table restaurant_categories
+    add column source varchar(16) not null default 'kakao' after categories,
+    add column confidence double not null default 1 after source;
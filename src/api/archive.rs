@@ -0,0 +1,208 @@
+//! Raw Kakao responses kept as gzip compressed JSON lines, one per page
+//! request, so places can be rebuilt later without spending quota.
+
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use super::{dto::*, Query};
+use crate::{
+    error,
+    types::{Area, Rect},
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    pub query: Query,
+    /// The area crawled, whose boundary cells also hold places outside it.
+    pub area: Area,
+    pub rect: Rect,
+    pub page: usize,
+    pub size: usize,
+    pub requested_at: chrono::DateTime<chrono::Utc>,
+    pub body: ResponseBody,
+}
+
+pub struct Archive {
+    w: GzEncoder<BufWriter<File>>,
+}
+
+impl Archive {
+    /// Appends to `path`. Every run adds a gzip member of its own, which
+    /// `replay` reads as one stream. An archive left cut short by a killed
+    /// run is rewritten without its partial record first, or nothing
+    /// appended after it could be read.
+    pub fn open(path: &Path) -> Result<Archive, error::Error> {
+        if path.metadata().is_ok_and(|m| m.len() > 0) {
+            let (records, truncated) = read_records(path)?;
+            if truncated {
+                repair(path, &records)?;
+            }
+        }
+        let file = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        Ok(Archive {
+            w: GzEncoder::new(BufWriter::new(file), Compression::default()),
+        })
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<(), error::Error> {
+        if let Err(e) = serde_json::to_writer(&mut self.w, record) {
+            return Err(error::Error::SerializationFailed(e));
+        }
+        match self.w.write_all(b"\n") {
+            Ok(..) => Ok(()),
+            Err(e) => Err(error::Error::FileIoFailed(e)),
+        }
+    }
+
+    /// Writes the gzip trailer. An archive of a run killed before it ends in
+    /// a truncated member, which `read` stops at, keeping the records before it.
+    pub fn finish(self) -> Result<(), error::Error> {
+        let mut w = match self.w.finish() {
+            Ok(w) => w,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        match w.flush() {
+            Ok(..) => Ok(()),
+            Err(e) => Err(error::Error::FileIoFailed(e)),
+        }
+    }
+}
+
+/// Reads every record of an archive in the order they were written. A
+/// member cut short by a killed run ends the archive, the records before it
+/// are kept.
+pub fn read(path: &Path) -> Result<Vec<Record>, error::Error> {
+    let (records, truncated) = read_records(path)?;
+    if truncated {
+        println!(
+            "{} is cut short, likely by an interrupted crawl, reading the {} records before that",
+            path.display(),
+            records.len()
+        );
+    }
+    Ok(records)
+}
+
+/// The records of an archive, and whether it ends in a truncated member.
+fn read_records(path: &Path) -> Result<(Vec<Record>, bool), error::Error> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(error::Error::FileIoFailed(e)),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(MultiGzDecoder::new(file)).lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok((records, true)),
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(r) => records.push(r),
+            Err(e) => return Err(error::Error::SerializationFailed(e)),
+        }
+    }
+    Ok((records, false))
+}
+
+/// Replaces the archive at `path` with `records` in a single member.
+fn repair(path: &Path, records: &[Record]) -> Result<(), error::Error> {
+    let tmp = path.with_extension("repair");
+    let file = match File::create(&tmp) {
+        Ok(f) => f,
+        Err(e) => return Err(error::Error::FileIoFailed(e)),
+    };
+    let mut archive = Archive {
+        w: GzEncoder::new(BufWriter::new(file), Compression::default()),
+    };
+    for r in records.iter() {
+        archive.write(r)?;
+    }
+    archive.finish()?;
+    match std::fs::rename(&tmp, path) {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+/// Rebuilds the places a crawl would have returned from archived responses,
+/// merged and clipped to the crawled area the same way the crawl does.
+pub fn replay(records: Vec<Record>) -> HashSet<Document> {
+    let mut documents = HashSet::new();
+    for r in records {
+        let found = r
+            .body
+            .documents
+            .into_iter()
+            .filter(|d| super::within(&r.area, d))
+            .collect();
+        super::collect(&mut documents, &r.query, found);
+    }
+    documents
+}
+
+#[test]
+fn test_read_truncated_archive() {
+    use super::Category;
+    let path = std::env::temp_dir().join(format!("{}.jsonl.gz", uuid::Uuid::new_v4()));
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let record = |page: usize| Record {
+        query: Query::Category(Category::Restaurant),
+        area: Area::Rect(rect.clone()),
+        rect: rect.clone(),
+        page,
+        size: 15,
+        requested_at: chrono::Utc::now(),
+        body: ResponseBody {
+            documents: [super::mock::document(
+                Category::Restaurant,
+                page,
+                126.92,
+                37.575,
+            )]
+            .into_iter()
+            .collect(),
+            meta: Meta {
+                is_end: true,
+                pageable_count: 1,
+                total_count: 1,
+            },
+        },
+    };
+    let append = |pages: std::ops::Range<usize>| {
+        let mut archive = Archive::open(&path).unwrap();
+        for page in pages {
+            archive.write(&record(page)).unwrap();
+        }
+        archive.finish().unwrap();
+    };
+
+    append(1..4);
+    append(4..7);
+    // a killed run leaves its member without the trailer and the end of its data
+    let len = std::fs::metadata(&path).unwrap().len();
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(len - 12).unwrap();
+    let pages: Vec<usize> = read(&path).unwrap().iter().map(|r| r.page).collect();
+    assert!(pages.starts_with(&[1, 2, 3]));
+    assert!(pages.len() < 6);
+
+    // the next run repairs the archive, so its records are read as well
+    append(7..9);
+    let pages: Vec<usize> = read(&path).unwrap().iter().map(|r| r.page).collect();
+    assert!(pages.starts_with(&[1, 2, 3]));
+    assert!(pages.ends_with(&[7, 8]));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,139 @@
+//! Crawl progress saved to a JSON file, so that a crawl that died halfway can
+//! be resumed without requesting the pages it already has again.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{dto::Document, CrawlOptions, CrawlResult, Job, Overflow, Query};
+use crate::{error, types::Area};
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The queries, area and cell settings of the crawl. A checkpoint is only
+    /// resumed by the same crawl.
+    pub crawl: String,
+    /// Page requests still to make, including the ones in flight or failed
+    /// when the checkpoint was saved.
+    pub(super) pending: Vec<Job>,
+    pub documents: Vec<Document>,
+    pub overflowed: Vec<Overflow>,
+    /// Successful requests so far.
+    pub requests: usize,
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Checkpoint {
+    /// Takes the state of a running crawl. Jobs in flight and failed jobs
+    /// are saved as pending, so a resumed crawl requests them again.
+    pub(super) fn new(
+        crawl: &str,
+        pending: &[Job],
+        in_flight: &HashMap<u64, Job>,
+        result: &CrawlResult,
+    ) -> Checkpoint {
+        let failed = result.failed.iter().map(|f| Job {
+            query: f.query.clone(),
+            rect: f.rect.clone(),
+            page: f.page,
+        });
+        Checkpoint {
+            crawl: crawl.to_string(),
+            pending: pending
+                .iter()
+                .chain(in_flight.values())
+                .cloned()
+                .chain(failed)
+                .collect(),
+            documents: result.documents.iter().cloned().collect(),
+            overflowed: result.overflowed.clone(),
+            requests: result.requests - result.failed.len(),
+            saved_at: chrono::Utc::now(),
+        }
+    }
+
+    /// The checkpoint a crawl starts from. Without `resume` an existing file
+    /// is refused rather than overwritten, with it a missing file starts a new crawl.
+    pub fn start(options: &CrawlOptions, crawl: &str) -> Result<Option<Checkpoint>, error::Error> {
+        let Some(path) = options.checkpoint.as_ref() else {
+            return Ok(None);
+        };
+        match (path.exists(), options.resume) {
+            (false, ..) => Ok(None),
+            (true, false) => Err(error::Error::InvalidArgument(format!(
+                "checkpoint {} exists, pass --resume to continue it or remove it",
+                path.display()
+            ))),
+            (true, true) => {
+                let checkpoint = Self::load(path)?;
+                if checkpoint.crawl != crawl {
+                    return Err(error::Error::InvalidArgument(format!(
+                        "checkpoint {} was saved by a crawl of other queries or another area",
+                        path.display()
+                    )));
+                }
+                Ok(Some(checkpoint))
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Checkpoint, error::Error> {
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(c) => Ok(c),
+            Err(e) => Err(error::Error::SerializationFailed(e)),
+        }
+    }
+
+    /// Writes to a temporary file first, so a crash while saving keeps the
+    /// previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> Result<(), error::Error> {
+        let mut tmp = PathBuf::from(path);
+        tmp.as_mut_os_string().push(".tmp");
+        let file = match File::create(&tmp) {
+            Ok(f) => f,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        let mut w = BufWriter::new(file);
+        if let Err(e) = serde_json::to_writer(&mut w, self) {
+            return Err(error::Error::SerializationFailed(e));
+        }
+        if let Err(e) = w.flush() {
+            return Err(error::Error::FileIoFailed(e));
+        }
+        match std::fs::rename(&tmp, path) {
+            Ok(..) => Ok(()),
+            Err(e) => Err(error::Error::FileIoFailed(e)),
+        }
+    }
+
+    /// Removes the checkpoint once its crawl has been fully used.
+    pub fn remove(path: &Path) -> Result<(), error::Error> {
+        match std::fs::remove_file(path) {
+            Ok(..) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(error::Error::FileIoFailed(e)),
+        }
+    }
+}
+
+/// What makes two crawls the same crawl for the purpose of resuming.
+pub fn fingerprint(
+    queries: &[Query],
+    area: &Area,
+    options: &CrawlOptions,
+) -> Result<String, error::Error> {
+    let value = (queries, area, options.page_size, options.min_cell_size);
+    match serde_json::to_string(&value) {
+        Ok(s) => Ok(s),
+        Err(e) => Err(error::Error::SerializationFailed(e)),
+    }
+}
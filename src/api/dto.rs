@@ -8,9 +8,12 @@ pub struct ResponseBody {
     pub meta: Meta,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Document {
     pub address_name: String,
+    /// Empty in responses of keyword searches without a category group.
+    #[serde(default)]
+    pub category_group_code: String,
     pub category_name: String,
     pub id: String,
     pub phone: String,
@@ -0,0 +1,281 @@
+//! A deterministic in-process stand-in for the Kakao local search API, so the
+//! crawl pipeline can be tested without network access or an API key.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use super::{
+    dto::{Document, Meta, ResponseBody},
+    Category,
+};
+use crate::types::{Coords, Rect};
+
+pub const API_KEY: &str = "test-key";
+
+/// Kakao never pages past this many documents per query.
+const MAX_PAGEABLE: usize = 45;
+
+#[derive(Default)]
+pub struct MockConfig {
+    pub places: Vec<(Category, Document)>,
+    /// Statuses answered, in order, to the first requests.
+    pub fail_first: Vec<u16>,
+    /// Every n-th request is answered with the given status.
+    pub fail_every: Option<(usize, u16)>,
+    /// `Retry-After` seconds sent along with injected failures.
+    pub retry_after: Option<u64>,
+}
+
+pub struct MockKakao {
+    addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+    handle: JoinHandle<()>,
+}
+
+impl MockKakao {
+    pub async fn start(config: MockConfig) -> MockKakao {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let config = Arc::new(config);
+        let counter = Arc::clone(&requests);
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, ..)) = listener.accept().await {
+                let (config, counter) = (Arc::clone(&config), Arc::clone(&counter));
+                tokio::spawn(async move { handle(stream, &config, &counter).await });
+            }
+        });
+        MockKakao {
+            addr,
+            requests,
+            handle,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/v2/local/search/category.json", self.addr)
+    }
+
+    pub fn keyword_url(&self) -> String {
+        format!("http://{}/v2/local/search/keyword.json", self.addr)
+    }
+
+    /// Requests received so far, including the ones answered with an injected failure.
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockKakao {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// `nx * ny` places spread evenly over `rect`, with ids starting at `first_id`.
+pub fn grid(
+    category: Category,
+    rect: &Rect,
+    nx: usize,
+    ny: usize,
+    first_id: usize,
+) -> Vec<(Category, Document)> {
+    let (w, h) = (rect.ne.x - rect.sw.x, rect.ne.y - rect.sw.y);
+    (0..nx * ny)
+        .map(|i| {
+            let x = rect.sw.x + w * ((i % nx) as f64 + 0.5) / nx as f64;
+            let y = rect.sw.y + h * ((i / nx) as f64 + 0.5) / ny as f64;
+            (category, document(category, first_id + i, x, y))
+        })
+        .collect()
+}
+
+/// `n` places sharing the exact same coordinate, which no subdivision can separate.
+pub fn cluster(
+    category: Category,
+    at: &Coords,
+    n: usize,
+    first_id: usize,
+) -> Vec<(Category, Document)> {
+    (0..n)
+        .map(|i| (category, document(category, first_id + i, at.x, at.y)))
+        .collect()
+}
+
+pub fn document(category: Category, id: usize, x: f64, y: f64) -> Document {
+    Document {
+        address_name: format!("서울 마포구 합정동 {id}"),
+        category_group_code: category.code(),
+        category_name: "음식점 > 한식".to_string(),
+        id: id.to_string(),
+        phone: format!("02-000-{id:04}"),
+        place_name: format!("place {id}"),
+        place_url: format!("http://place.map.kakao.com/{id}"),
+        road_address_name: format!("서울 마포구 양화로 {id}"),
+        x: x.to_string(),
+        y: y.to_string(),
+    }
+}
+
+async fn handle(mut stream: TcpStream, config: &MockConfig, counter: &AtomicUsize) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+    let (status, body) = respond(&request, n, config);
+    let retry_after = match (status, config.retry_after) {
+        (200, ..) | (.., None) => String::new(),
+        (.., Some(secs)) => format!("Retry-After: {secs}\r\n"),
+    };
+    let response = format!(
+        "HTTP/1.1 {status} MOCK\r\nContent-Type: application/json;charset=UTF-8\r\nContent-Length: {}\r\n{retry_after}Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+struct Request {
+    path: String,
+    query: HashMap<String, String>,
+    authorization: Option<String>,
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&buf).to_string();
+    let mut lines = head.split("\r\n");
+    let target = lines.next()?.split(' ').nth(1)?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, q)) => (path, q),
+        None => (target, ""),
+    };
+    let query = match query {
+        "" => HashMap::new(),
+        q => q
+            .split('&')
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (decode(k), decode(v)))
+            .collect(),
+    };
+    let authorization = lines
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, ..)| k.eq_ignore_ascii_case("authorization"))
+        .map(|(.., v)| v.trim().to_string());
+    Some(Request {
+        path: path.to_string(),
+        query,
+        authorization,
+    })
+}
+
+fn respond(request: &Request, n: usize, config: &MockConfig) -> (u16, String) {
+    let injected = match config.fail_every {
+        Some((every, status)) if n.is_multiple_of(every) => Some(status),
+        _ => config.fail_first.get(n - 1).copied(),
+    };
+    if let Some(status) = injected {
+        return (status, error_body("injected failure"));
+    }
+    if request.authorization.as_deref() != Some(format!("KakaoAK {API_KEY}").as_str()) {
+        return (401, error_body("wrong appkey"));
+    }
+
+    let param = |k: &str| request.query.get(k).map(String::as_str);
+    let Some(rect) = param("rect").and_then(parse_rect) else {
+        return (400, error_body("rect is required"));
+    };
+    let page: usize = param("page").and_then(|v| v.parse().ok()).unwrap_or(1);
+    let size: usize = param("size").and_then(|v| v.parse().ok()).unwrap_or(15);
+    let code = param("category_group_code").unwrap_or_default();
+    let keyword = param("query").unwrap_or_default();
+    let by_keyword = request.path.ends_with("/keyword.json");
+    if by_keyword && keyword.is_empty() {
+        return (400, error_body("query is required"));
+    }
+
+    let mut matches: Vec<&Document> = config
+        .places
+        .iter()
+        .filter(|(c, d)| match by_keyword {
+            true => d.place_name.contains(keyword) || d.category_name.contains(keyword),
+            false => c.code() == code,
+        })
+        .map(|(.., d)| d)
+        .filter(|d| {
+            let (x, y): (f64, f64) = (d.x.parse().unwrap(), d.y.parse().unwrap());
+            (rect.0..=rect.2).contains(&x) && (rect.1..=rect.3).contains(&y)
+        })
+        .collect();
+    matches.sort_by_key(|d| d.id.parse::<usize>().unwrap_or_default());
+
+    let total_count = matches.len();
+    let pageable_count = total_count.min(MAX_PAGEABLE);
+    let from = (page.saturating_sub(1) * size).min(pageable_count);
+    let to = (page * size).min(pageable_count);
+    let body = ResponseBody {
+        documents: matches[from..to].iter().map(|d| (*d).clone()).collect(),
+        meta: Meta {
+            is_end: to >= pageable_count,
+            pageable_count,
+            total_count,
+        },
+    };
+    (200, serde_json::to_string(&body).unwrap())
+}
+
+fn parse_rect(s: &str) -> Option<(f64, f64, f64, f64)> {
+    let v: Vec<f64> = s.split(',').filter_map(|v| v.parse().ok()).collect();
+    match v[..] {
+        [a, b, c, d] => Some((a, b, c, d)),
+        _ => None,
+    }
+}
+
+fn error_body(message: &str) -> String {
+    format!("{{\"errorType\":\"MockError\",\"message\":\"{message}\"}}")
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    Err(..) => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
@@ -1,140 +1,543 @@
+pub mod archive;
+mod checkpoint;
 pub mod dto;
+#[cfg(test)]
+pub(crate) mod mock;
+mod provider;
+mod rate_limit;
+mod retry;
 
+use archive::Archive;
+pub use checkpoint::Checkpoint;
 #[allow(unused_imports)]
 use dotenv::dotenv;
 use dto::*;
-use std::collections::HashSet;
+pub use provider::PlaceProvider;
+use rate_limit::RateLimiter;
+use retry::RetryPolicy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::{
+    task::JoinSet,
+    time::{Duration, Instant},
+};
 
-use crate::{types::*, utils};
+use crate::{config::Config, error, types::*};
 
-macro_rules! unwrap_result_or {
-    ($e: expr, $or: expr) => {
-        match $e {
-            Ok(v) => v,
-            Err(..) => $or,
+pub struct CrawlOptions {
+    pub page_size: usize,
+    /// Cells whose longer side is at most this many meters are not split any further.
+    pub min_cell_size: f64,
+    /// Share of failed requests above which the whole crawl is treated as failed
+    /// instead of returning a partial result.
+    pub max_failure_ratio: f64,
+    pub requests_per_second: f64,
+    /// Requests this run may spend in total, to stay inside the daily quota.
+    pub daily_limit: Option<usize>,
+    pub max_retries: u32,
+    pub timeout: Duration,
+    /// Requests in flight at once, all sharing the same rate limiter.
+    pub concurrency: usize,
+    pub retry_base_delay: Duration,
+    /// File the crawl progress is saved to, every `checkpoint_interval` and
+    /// whenever the crawl stops early.
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    /// Continue from `checkpoint` instead of refusing to overwrite it.
+    pub resume: bool,
+    /// Compressed JSON lines file every successful response is appended to.
+    pub archive: Option<PathBuf>,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        CrawlOptions {
+            page_size: 15,
+            min_cell_size: 50.0,
+            max_failure_ratio: 0.05,
+            requests_per_second: 10.0,
+            daily_limit: None,
+            max_retries: 5,
+            timeout: Duration::from_secs(10),
+            concurrency: 4,
+            retry_base_delay: Duration::from_millis(500),
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(30),
+            resume: false,
+            archive: None,
         }
-    };
+    }
 }
 
-pub async fn get_from_kakao(sw: Coords, ne: Coords) -> HashSet<Document> {
-    let kakao = Kakao::new();
-    let mut restaurants = kakao
-        .get(&Category::Restaurant, sw.x, sw.y, ne.x, ne.y)
-        .await;
-    println!("{:?}: {}", Category::Restaurant, restaurants.len());
-    let cafe = kakao.get(&Category::Cafe, sw.x, sw.y, ne.x, ne.y).await;
-    println!("{:?}: {}", Category::Cafe, cafe.len());
-    restaurants.extend(cafe);
-    restaurants
+/// A cell that still had more results than Kakao pages through (45), but was
+/// too small to split. Only its first `pageable_count` documents were collected.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Overflow {
+    pub query: Query,
+    pub rect: Rect,
+    pub total_count: usize,
 }
 
-struct Kakao {
-    client: reqwest::Client,
+/// A page request that failed. The rest of the cell (and for the first page,
+/// its sub cells) was not crawled.
+#[derive(Debug)]
+pub struct Failure {
+    pub query: Query,
+    pub rect: Rect,
+    pub page: usize,
+    pub error: error::Error,
 }
 
-impl Kakao {
-    pub fn new() -> Kakao {
-        Kakao {
-            client: reqwest::Client::new(),
+#[derive(Default)]
+pub struct CrawlResult {
+    pub documents: HashSet<Document>,
+    pub overflowed: Vec<Overflow>,
+    pub failed: Vec<Failure>,
+    pub requests: usize,
+}
+
+impl CrawlResult {
+    /// Records a failed request, unless the error means no further request can succeed.
+    /// Quota errors only get here once retries are exhausted, so the quota is gone for the day.
+    fn fail(
+        &mut self,
+        query: &Query,
+        rect: &Rect,
+        page: usize,
+        error: error::Error,
+    ) -> Result<(), error::Error> {
+        if let error::Error::ApiUnauthorized(..) | error::Error::ApiQuotaExceeded(..) = error {
+            return Err(error);
         }
+        self.failed.push(Failure {
+            query: query.clone(),
+            rect: rect.clone(),
+            page,
+            error,
+        });
+        Ok(())
     }
 
-    async fn get(
-        &self,
-        category: &Category,
-        swx: f64,
-        swy: f64,
-        nex: f64,
-        ney: f64,
-    ) -> HashSet<Document> {
-        let mut stack = vec![(swx, swy, nex, ney)];
-        let mut result = HashSet::new();
-
-        while let Some((swx, swy, nex, ney)) = stack.pop() {
-            let mut page = 1;
-            let size = 15;
-            let body = unwrap_result_or!(
-                self.get_body(category, swx, swy, nex, ney, page, size)
-                    .await,
-                continue
+    pub fn failure_ratio(&self) -> f64 {
+        match self.requests {
+            0 => 0.0,
+            n => self.failed.len() as f64 / n as f64,
+        }
+    }
+}
+
+pub async fn get_from_kakao(config: &Config, area: &Area) -> Result<CrawlResult, error::Error> {
+    let options = config.crawl_options();
+    let kakao = Kakao::from_config(config)?;
+    let queries: Vec<Query> = config
+        .groups()?
+        .into_iter()
+        .map(Query::Category)
+        .chain(config.crawl.keywords.iter().cloned().map(Query::Keyword))
+        .collect();
+    let result = crawl(Arc::new(kakao), &queries, area, &options).await;
+    if options.checkpoint.is_some() {
+        // The crawl handled Ctrl-C itself, from here on it ends the process again.
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        });
+    }
+    result
+}
+
+/// Crawls every query over `rect` with up to `options.concurrency` requests
+/// in flight. First pages decide whether a cell is split or paged through,
+/// and the remaining pages of a cell are then fetched concurrently as well.
+/// Places found by several queries are kept once.
+///
+/// Cells are cut from the bounding rectangle of `area`. Sub cells outside of
+/// `area` are not requested, and places outside of it are dropped.
+///
+/// With `options.checkpoint` set, the progress is saved periodically, when
+/// the crawl fails and on Ctrl-C, and a resumed crawl starts from it.
+pub async fn crawl<P: PlaceProvider>(
+    provider: Arc<P>,
+    queries: &[Query],
+    area: &Area,
+    options: &CrawlOptions,
+) -> Result<CrawlResult, error::Error> {
+    let bounds = area.bounds()?;
+    let fingerprint = checkpoint::fingerprint(queries, area, options)?;
+    let archive = match options.archive.as_ref() {
+        Some(path) => Some(Archive::open(path)?),
+        None => None,
+    };
+    let mut progress = match Checkpoint::start(options, &fingerprint)? {
+        Some(c) => {
+            println!(
+                "resuming crawl saved at {}: {} places, {} requests left",
+                c.saved_at,
+                c.documents.len(),
+                c.pending.len()
             );
-            let (documents, meta) = (body.documents, body.meta);
-            let (pageable_count, total_count, ..) =
-                (meta.pageable_count, meta.total_count, meta.is_end);
-            // println!("depth: {}, {meta:?}", stack.len());
-            if pageable_count < total_count {
-                let w = (nex - swy).abs();
-                let h = (ney - swy).abs();
-                match w > h {
-                    true => {
-                        stack.push((swx, swy, (nex + swx) / 2.0, ney));
-                        stack.push(((nex + swx) / 2.0, swy, nex, ney));
-                    }
-                    false => {
-                        stack.push((swx, (ney + swy) / 2.0, nex, ney));
-                        stack.push((swx, swy, nex, (ney + swy) / 2.0));
+            Progress {
+                pending: c.pending,
+                in_flight: HashMap::new(),
+                result: CrawlResult {
+                    documents: c.documents.into_iter().collect(),
+                    overflowed: c.overflowed,
+                    failed: Vec::new(),
+                    requests: c.requests,
+                },
+                archive,
+            }
+        }
+        None => Progress {
+            pending: queries
+                .iter()
+                .map(|query| Job {
+                    query: query.clone(),
+                    rect: bounds.clone(),
+                    page: 1,
+                })
+                .collect(),
+            in_flight: HashMap::new(),
+            result: CrawlResult::default(),
+            archive,
+        },
+    };
+
+    let crawled = progress.run(provider, area, options, &fingerprint).await;
+    if let Some(path) = options.checkpoint.as_ref() {
+        Checkpoint::new(
+            &fingerprint,
+            &progress.pending,
+            &progress.in_flight,
+            &progress.result,
+        )
+        .save(path)?;
+    }
+    if let Some(archive) = progress.archive.take() {
+        archive.finish()?;
+    }
+    crawled?;
+
+    let mut result = progress.result;
+    result.documents.retain(|d| within(area, d));
+
+    if result.failure_ratio() > options.max_failure_ratio {
+        return Err(error::Error::TooManyFailures {
+            failed: result.failed.len(),
+            requests: result.requests,
+        });
+    }
+    Ok(result)
+}
+
+/// The state of a running crawl, everything a checkpoint is made of.
+struct Progress {
+    pending: Vec<Job>,
+    /// Jobs spawned but not joined yet, by the sequence number they were
+    /// spawned with.
+    in_flight: HashMap<u64, Job>,
+    result: CrawlResult,
+    archive: Option<Archive>,
+}
+
+impl Progress {
+    /// Runs jobs until none are left. On an error the job that failed is
+    /// pending again, so the saved progress still covers it.
+    async fn run<P: PlaceProvider>(
+        &mut self,
+        provider: Arc<P>,
+        area: &Area,
+        options: &CrawlOptions,
+        fingerprint: &str,
+    ) -> Result<(), error::Error> {
+        let size = options.page_size;
+        let mut running = JoinSet::new();
+        let mut seq = 0_u64;
+        let mut saved = Instant::now();
+        // Ctrl-C only stops the crawl gracefully when there is a file to save to.
+        let interrupted = async {
+            match options.checkpoint {
+                Some(..) => tokio::signal::ctrl_c().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(interrupted);
+
+        loop {
+            while running.len() < options.concurrency {
+                let Some(job) = self.pending.pop() else { break };
+                let provider = Arc::clone(&provider);
+                seq += 1;
+                self.in_flight.insert(seq, job.clone());
+                running.spawn(async move {
+                    let body = provider.fetch(&job.query, &job.rect, job.page, size).await;
+                    (seq, job, body)
+                });
+            }
+            let next = tokio::select! {
+                next = running.join_next() => next,
+                _ = &mut interrupted => return Err(error::Error::Interrupted),
+            };
+            let (id, job, body) = match next {
+                Some(Ok(v)) => v,
+                Some(Err(e)) => return Err(error::Error::TaskFailed(e)),
+                None => break,
+            };
+            self.in_flight.remove(&id);
+            self.result.requests += 1;
+            if let Err(e) = self.handle(&job, body, area, options) {
+                self.result.requests -= 1;
+                self.pending.push(job);
+                return Err(e);
+            }
+
+            if let Some(path) = options.checkpoint.as_ref() {
+                if saved.elapsed() >= options.checkpoint_interval {
+                    Checkpoint::new(fingerprint, &self.pending, &self.in_flight, &self.result)
+                        .save(path)?;
+                    saved = Instant::now();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(
+        &mut self,
+        job: &Job,
+        body: Result<ResponseBody, error::Error>,
+        area: &Area,
+        options: &CrawlOptions,
+    ) -> Result<(), error::Error> {
+        let size = options.page_size;
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => return self.result.fail(&job.query, &job.rect, job.page, e),
+        };
+        let body = match self.archive.as_mut() {
+            Some(archive) => {
+                let record = archive::Record {
+                    query: job.query.clone(),
+                    area: area.clone(),
+                    rect: job.rect.clone(),
+                    page: job.page,
+                    size,
+                    requested_at: chrono::Utc::now(),
+                    body,
+                };
+                archive.write(&record)?;
+                record.body
+            }
+            None => body,
+        };
+        if job.page == 1 {
+            let meta = &body.meta;
+            if meta.pageable_count < meta.total_count {
+                if job.rect.longer_side() > options.min_cell_size {
+                    let (a, b) = job.rect.split();
+                    for rect in [a, b].into_iter().filter(|r| area.intersects(r)) {
+                        self.pending.push(Job {
+                            rect,
+                            ..job.clone()
+                        });
                     }
+                    return Ok(());
                 }
-                continue;
+                self.result.overflowed.push(Overflow {
+                    query: job.query.clone(),
+                    rect: job.rect.clone(),
+                    total_count: meta.total_count,
+                });
             }
-            let mut remain: isize = (pageable_count - documents.len()) as isize;
-            // println!("{remain} remains. {page}th page will be req");
-            result.extend(documents);
-            page += 1;
-            while remain > 0 {
-                let body = unwrap_result_or!(
-                    self.get_body(category, swx, swy, nex, ney, page, size)
-                        .await,
-                    break
-                );
-                let documents = body.documents;
-                result.extend(documents);
-                remain -= size as isize;
-                // println!("{remain} remains. {page}th page will be req");
-                if body.meta.is_end {
-                    break;
+            let pages = meta.pageable_count.div_ceil(size);
+            self.pending.extend((2..=pages).map(|page| Job {
+                page,
+                ..job.clone()
+            }));
+        }
+        collect(&mut self.result.documents, &job.query, body.documents);
+        Ok(())
+    }
+}
+
+/// Whether a place lies inside `area`. Cells at the boundary of circles and
+/// polygons reach past it. Places without readable coordinates are kept and
+/// left to the caller.
+fn within(area: &Area, d: &Document) -> bool {
+    match (d.x.parse(), d.y.parse()) {
+        (Ok(x), Ok(y)) => area.contains(x, y),
+        _ => true,
+    }
+}
+
+/// Adds the documents of one response to the places found so far.
+fn collect(found: &mut HashSet<Document>, query: &Query, documents: HashSet<Document>) {
+    for mut d in documents {
+        match query {
+            Query::Category(c) => {
+                if d.category_group_code.is_empty() {
+                    d.category_group_code = c.code();
                 }
-                page += 1;
+                // Category results carry the group, so they win over
+                // keyword results of the same place.
+                found.replace(d);
+            }
+            Query::Keyword(..) => {
+                found.insert(d);
             }
-            // println!("{}", result.len());
         }
+    }
+}
 
-        result
+/// One page request of one cell.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Job {
+    query: Query,
+    rect: Rect,
+    page: usize,
+}
+
+struct Kakao {
+    client: reqwest::Client,
+    category_url: String,
+    keyword_url: String,
+    api_key: String,
+    limiter: RateLimiter,
+    retry: RetryPolicy,
+}
+
+impl PlaceProvider for Kakao {
+    async fn fetch(
+        &self,
+        query: &Query,
+        rect: &Rect,
+        page: usize,
+        size: usize,
+    ) -> Result<ResponseBody, error::Error> {
+        self.get_body(query, rect, page, size).await
+    }
+}
+
+impl Kakao {
+    pub fn new(
+        category_url: String,
+        keyword_url: String,
+        api_key: String,
+        options: &CrawlOptions,
+    ) -> Result<Kakao, error::Error> {
+        let client = match reqwest::Client::builder().timeout(options.timeout).build() {
+            Ok(c) => c,
+            Err(e) => return Err(error::Error::ApiRequestFailed(e)),
+        };
+        Ok(Kakao {
+            client,
+            category_url,
+            keyword_url,
+            api_key,
+            limiter: RateLimiter::new(options.requests_per_second, options.daily_limit),
+            retry: RetryPolicy {
+                max_retries: options.max_retries,
+                base_delay: options.retry_base_delay,
+                max_delay: Duration::from_secs(30),
+            },
+        })
     }
 
-    #[allow(clippy::too_many_arguments)]
     async fn get_body(
         &self,
-        category: &Category,
-        swx: f64,
-        swy: f64,
-        nex: f64,
-        ney: f64,
+        query: &Query,
+        rect: &Rect,
+        page: usize,
+        size: usize,
+    ) -> Result<ResponseBody, error::Error> {
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await?;
+            match self.request(query, rect, page, size).await {
+                Ok(body) => return Ok(body),
+                Err((e, retry_after))
+                    if attempt < self.retry.max_retries && RetryPolicy::is_retryable(&e) =>
+                {
+                    match self.retry.delay(attempt, retry_after) {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => return Err(e),
+                    }
+                    attempt += 1;
+                }
+                Err((e, ..)) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends a single request. Errors come with the `Retry-After` the server asked for.
+    async fn request(
+        &self,
+        query: &Query,
+        rect: &Rect,
         page: usize,
         size: usize,
-    ) -> Result<ResponseBody, Box<dyn std::error::Error>> {
-        Ok(self
+    ) -> Result<ResponseBody, (error::Error, Option<Duration>)> {
+        let (sw, ne) = (&rect.sw, &rect.ne);
+        let (url, param) = match query {
+            Query::Category(c) => (&self.category_url, ("category_group_code", c.code())),
+            Query::Keyword(k) => (&self.keyword_url, ("query", k.clone())),
+        };
+        let response = self
             .client
-            .get(Self::url())
-            .header("Authorization", format!("KakaoAK {}", Self::api_key()))
+            .get(url)
+            .header("Authorization", format!("KakaoAK {}", self.api_key))
             .query(&[
-                ("category_group_code", category.code().as_str()),
-                ("rect", format!("{swx},{swy},{nex},{ney}").as_str()),
+                (param.0, param.1.as_str()),
+                (
+                    "rect",
+                    format!("{},{},{},{}", sw.x, sw.y, ne.x, ne.y).as_str(),
+                ),
                 ("page", page.to_string().as_str()),
                 ("size", size.to_string().as_str()),
             ])
             .send()
-            .await?
-            .json::<ResponseBody>()
-            .await?)
-    }
-
-    fn url() -> String {
-        utils::Const::KakaoRestApiUrl.value()
+            .await;
+        let response = match response {
+            Ok(r) => r,
+            Err(e) => return Err((error::Error::ApiRequestFailed(e), None)),
+        };
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let text = match response.text().await {
+            Ok(t) => t,
+            Err(e) => return Err((error::Error::ApiRequestFailed(e), retry_after)),
+        };
+        let error = match status.as_u16() {
+            200..=299 => {
+                return match serde_json::from_str::<ResponseBody>(&text) {
+                    Ok(body) => Ok(body),
+                    Err(e) => Err((error::Error::ApiDecodeFailed(e), None)),
+                }
+            }
+            401 | 403 => error::Error::ApiUnauthorized(text),
+            429 => error::Error::ApiQuotaExceeded(text),
+            code => error::Error::ApiStatus(code, text),
+        };
+        Err((error, retry_after))
     }
 
-    fn api_key() -> String {
-        utils::Const::KakaoRestApiKey.value()
+    fn from_config(config: &Config) -> Result<Kakao, error::Error> {
+        Self::new(
+            config.kakao.api_url.clone(),
+            config.kakao.keyword_url.clone(),
+            config.api_key()?.to_string(),
+            &config.crawl_options(),
+        )
     }
 }
 
@@ -142,7 +545,7 @@ impl Kakao {
 fn test_url() {
     dotenv().ok();
     assert_eq!(
-        Kakao::url(),
+        Config::load(None).unwrap().kakao.api_url,
         "https://dapi.kakao.com/v2/local/search/category.json"
     )
 }
@@ -151,14 +554,11 @@ fn test_url() {
 #[test]
 async fn test_get_body() {
     dotenv().ok();
-    let kakao_client = Kakao::new();
+    let kakao_client = Kakao::from_config(&Config::load(None).unwrap()).unwrap();
     let meta = kakao_client
         .get_body(
-            &Category::Restaurant,
-            126.916080,
-            37.574244,
-            126.928096,
-            37.584311,
+            &Query::Category(Category::Restaurant),
+            &Rect::from_corners(126.916080, 37.574244, 126.928096, 37.584311).unwrap(),
             1,
             15,
         )
@@ -168,11 +568,8 @@ async fn test_get_body() {
     println!("{meta:?}");
     let meta = kakao_client
         .get_body(
-            &Category::Restaurant,
-            126.916080,
-            37.574244,
-            126.920000,
-            37.58,
+            &Query::Category(Category::Restaurant),
+            &Rect::from_corners(126.916080, 37.574244, 126.920000, 37.58).unwrap(),
             1,
             15,
         )
@@ -187,19 +584,22 @@ async fn test_get_body() {
 async fn test_get_by_category() {
     use std::collections::HashMap;
     dotenv().ok();
-    let kakao_client = Kakao::new();
-    let result = kakao_client
-        .get(
-            &Category::Cafe,
-            126.907418,
-            37.569670,
-            126.938746,
-            37.585196,
-        )
-        .await;
+    let kakao_client = Kakao::from_config(&Config::load(None).unwrap()).unwrap();
+    let result = crawl(
+        Arc::new(kakao_client),
+        &[Query::Category(Category::Cafe)],
+        &Area::Rect(Rect::from_corners(126.907418, 37.569670, 126.938746, 37.585196).unwrap()),
+        &CrawlOptions {
+            max_failure_ratio: 0.0,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap()
+    .documents;
     let mut group = HashMap::new();
     result.iter().for_each(|d| {
-        let parsed = match d.category_name.split(" > ").skip(1).next() {
+        let parsed = match d.category_name.split(" > ").nth(1) {
             Some(v) => v,
             None => d.category_name.as_str(),
         };
@@ -217,8 +617,24 @@ async fn test_get_by_category() {
     // println!("{result:?}");
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+/// What a crawl searches for in every cell: a category group through
+/// `category.json`, or a search term through `keyword.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Query {
+    Category(Category),
+    Keyword(String),
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Category(c) => write!(f, "{}", c.code()),
+            Self::Keyword(k) => write!(f, "keyword \"{k}\""),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Category {
     Supermarket,
     ConvinienceStore,
@@ -265,3 +681,446 @@ impl Category {
         .to_owned()
     }
 }
+
+impl Category {
+    /// Groups whose places belong in the restaurant table.
+    pub fn is_food(&self) -> bool {
+        matches!(self, Self::Restaurant | Self::Cafe)
+    }
+}
+
+impl std::str::FromStr for Category {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "MT1" => Ok(Self::Supermarket),
+            "CS2" => Ok(Self::ConvinienceStore),
+            "PS3" => Ok(Self::Kindergarden),
+            "SC4" => Ok(Self::School),
+            "AC5" => Ok(Self::Academy),
+            "PK6" => Ok(Self::ParkingLot),
+            "OL7" => Ok(Self::GasStation),
+            "SW8" => Ok(Self::SubwayStation),
+            "BK9" => Ok(Self::Bank),
+            "CT1" => Ok(Self::CulturalFactilities),
+            "AG2" => Ok(Self::Brokerage),
+            "PO3" => Ok(Self::PublicInstitutions),
+            "AT4" => Ok(Self::Attractions),
+            "AD5" => Ok(Self::Lodgment),
+            "FD6" => Ok(Self::Restaurant),
+            "CE7" => Ok(Self::Cafe),
+            "HP8" => Ok(Self::Hospital),
+            "PM9" => Ok(Self::Pharmacy),
+            _ => Err(error::Error::InvalidArgument(format!(
+                "unknown category group code {s}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+async fn crawl_mock(
+    server: &mock::MockKakao,
+    groups: &[Category],
+    rect: &Rect,
+    options: &CrawlOptions,
+) -> Result<CrawlResult, error::Error> {
+    let queries: Vec<Query> = groups.iter().copied().map(Query::Category).collect();
+    let area = Area::Rect(rect.clone());
+    crawl(mock_kakao(server, options), &queries, &area, options).await
+}
+
+#[cfg(test)]
+fn mock_kakao(server: &mock::MockKakao, options: &CrawlOptions) -> Arc<Kakao> {
+    let kakao = Kakao::new(
+        server.url(),
+        server.keyword_url(),
+        mock::API_KEY.to_string(),
+        options,
+    );
+    Arc::new(kakao.unwrap())
+}
+
+#[cfg(test)]
+fn mock_options() -> CrawlOptions {
+    CrawlOptions {
+        requests_per_second: 1000.0,
+        retry_base_delay: Duration::from_millis(1),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_crawl_finds_every_place() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let mut places = mock::grid(Category::Restaurant, &rect, 30, 20, 0);
+    places.extend(mock::grid(Category::Cafe, &rect, 10, 10, 1000));
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+
+    let result = crawl_mock(
+        &server,
+        &[Category::Restaurant, Category::Cafe],
+        &rect,
+        &mock_options(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.documents.len(), 700);
+    assert!(result.overflowed.is_empty());
+    assert!(result.failed.is_empty());
+    assert_eq!(result.requests, server.requests());
+}
+
+#[tokio::test]
+async fn test_crawl_non_food_groups() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let mut places = mock::grid(Category::Pharmacy, &rect, 5, 4, 0);
+    places.extend(mock::grid(Category::SubwayStation, &rect, 2, 1, 100));
+    places.extend(mock::grid(Category::Restaurant, &rect, 3, 3, 200));
+    let mut subway = places.iter_mut().map(|(.., d)| d).filter(|d| d.id == "100");
+    subway.next().unwrap().category_group_code = String::new();
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+
+    let groups = [Category::Pharmacy, Category::SubwayStation];
+    let result = crawl_mock(&server, &groups, &rect, &mock_options())
+        .await
+        .unwrap();
+    assert_eq!(result.documents.len(), 22);
+    let codes: HashSet<&str> = result
+        .documents
+        .iter()
+        .map(|d| d.category_group_code.as_str())
+        .collect();
+    assert_eq!(codes, HashSet::from(["PM9", "SW8"]));
+}
+
+#[tokio::test]
+async fn test_crawl_by_keyword() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let mut places = mock::grid(Category::Restaurant, &rect, 10, 10, 0);
+    places.extend(mock::grid(Category::Attractions, &rect, 10, 6, 1000));
+    for (c, d) in places.iter_mut() {
+        let id: usize = d.id.parse().unwrap();
+        if *c == Category::Attractions || id.is_multiple_of(20) {
+            d.place_name = format!("푸드트럭 {id}");
+        }
+        if *c == Category::Attractions {
+            d.category_group_code = String::new();
+        }
+    }
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+
+    let queries = [
+        Query::Category(Category::Restaurant),
+        Query::Keyword("푸드트럭".to_string()),
+    ];
+    let options = mock_options();
+    let area = Area::Rect(rect);
+    let result = crawl(mock_kakao(&server, &options), &queries, &area, &options)
+        .await
+        .unwrap();
+    assert_eq!(result.documents.len(), 160);
+    assert!(result.overflowed.is_empty());
+    let keyword_only = result
+        .documents
+        .iter()
+        .filter(|d| d.category_group_code.is_empty())
+        .count();
+    assert_eq!(keyword_only, 60);
+}
+
+#[tokio::test]
+async fn test_crawl_circle() {
+    let circle = Circle::new(Coords::new(126.9237, 37.5563).unwrap(), 800.0).unwrap();
+    let bounds = circle.bounds().unwrap();
+    let places = mock::grid(Category::Restaurant, &bounds, 30, 30, 0);
+    let inside: HashSet<String> = places
+        .iter()
+        .map(|(.., d)| d)
+        .filter(|d| circle.contains(d.x.parse().unwrap(), d.y.parse().unwrap()))
+        .map(|d| d.id.clone())
+        .collect();
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+
+    let path = std::env::temp_dir().join(format!("{}.jsonl.gz", uuid::Uuid::new_v4()));
+    let options = CrawlOptions {
+        archive: Some(path.clone()),
+        ..mock_options()
+    };
+    let queries = [Query::Category(Category::Restaurant)];
+    let area = Area::Circle(circle);
+    let result = crawl(mock_kakao(&server, &options), &queries, &area, &options)
+        .await
+        .unwrap();
+    let found: HashSet<String> = result.documents.iter().map(|d| d.id.clone()).collect();
+    assert!(inside.len() < 900);
+    assert_eq!(found, inside);
+    assert!(result.overflowed.is_empty());
+
+    // boundary cells are archived whole, replay clips them like the crawl
+    let replayed = archive::replay(archive::read(&path).unwrap());
+    let replayed: HashSet<String> = replayed.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(replayed, inside);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_crawl_polygon() {
+    let triangle = Polygon::from_geojson(
+        r#"{"type": "Polygon", "coordinates": [[[126.91, 37.57], [126.93, 37.57], [126.91, 37.59], [126.91, 37.57]]]}"#,
+    )
+    .unwrap();
+    let places = mock::grid(Category::Restaurant, &triangle.bounds().unwrap(), 40, 40, 0);
+    let inside: HashSet<String> = places
+        .iter()
+        .map(|(.., d)| d)
+        .filter(|d| triangle.contains(d.x.parse().unwrap(), d.y.parse().unwrap()))
+        .map(|d| d.id.clone())
+        .collect();
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+
+    let options = mock_options();
+    let queries = [Query::Category(Category::Restaurant)];
+    let area = Area::Polygon(triangle);
+    let result = crawl(mock_kakao(&server, &options), &queries, &area, &options)
+        .await
+        .unwrap();
+    let found: HashSet<String> = result.documents.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(found, inside);
+    assert!(result.overflowed.is_empty());
+}
+
+#[tokio::test]
+async fn test_crawl_records_overflow() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let mut places = mock::grid(Category::Restaurant, &rect, 4, 4, 0);
+    let hotspot = Coords::new(126.9201, 37.5751).unwrap();
+    places.extend(mock::cluster(Category::Restaurant, &hotspot, 60, 100));
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+
+    let result = crawl_mock(&server, &[Category::Restaurant], &rect, &mock_options())
+        .await
+        .unwrap();
+    assert_eq!(result.overflowed.len(), 1);
+    let overflow = &result.overflowed[0];
+    assert_eq!(overflow.total_count, 60);
+    assert!(overflow.rect.longer_side() <= 50.0);
+    assert_eq!(result.documents.len(), 16 + 45);
+}
+
+#[tokio::test]
+async fn test_crawl_retries_transient_failures() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places: mock::grid(Category::Restaurant, &rect, 10, 10, 0),
+        fail_first: vec![500, 503, 429],
+        retry_after: Some(0),
+        ..Default::default()
+    })
+    .await;
+
+    let result = crawl_mock(&server, &[Category::Restaurant], &rect, &mock_options())
+        .await
+        .unwrap();
+    assert_eq!(result.documents.len(), 100);
+    assert!(result.failed.is_empty());
+    assert_eq!(server.requests(), result.requests + 3);
+}
+
+#[tokio::test]
+async fn test_crawl_gives_up_on_long_retry_after() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places: mock::grid(Category::Restaurant, &rect, 10, 10, 0),
+        fail_first: vec![429],
+        retry_after: Some(6 * 60 * 60),
+        ..Default::default()
+    })
+    .await;
+
+    let options = mock_options();
+    let crawl = crawl_mock(&server, &[Category::Restaurant], &rect, &options);
+    let result = tokio::time::timeout(Duration::from_secs(10), crawl)
+        .await
+        .unwrap();
+    assert!(matches!(result, Err(error::Error::ApiQuotaExceeded(..))));
+    assert_eq!(server.requests(), 1);
+}
+
+#[tokio::test]
+async fn test_crawl_fails_above_failure_ratio() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let config = || mock::MockConfig {
+        places: mock::grid(Category::Restaurant, &rect, 30, 30, 0),
+        fail_every: Some((4, 400)),
+        ..Default::default()
+    };
+
+    let server = mock::MockKakao::start(config()).await;
+    let result = crawl_mock(&server, &[Category::Restaurant], &rect, &mock_options()).await;
+    assert!(matches!(result, Err(error::Error::TooManyFailures { .. })));
+
+    let server = mock::MockKakao::start(config()).await;
+    let options = CrawlOptions {
+        max_failure_ratio: 0.5,
+        ..mock_options()
+    };
+    let result = crawl_mock(&server, &[Category::Restaurant], &rect, &options)
+        .await
+        .unwrap();
+    assert!(!result.failed.is_empty());
+    assert!(result
+        .failed
+        .iter()
+        .all(|f| matches!(f.error, error::Error::ApiStatus(400, ..))));
+}
+
+#[tokio::test]
+async fn test_crawl_aborts_on_wrong_key() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places: mock::grid(Category::Restaurant, &rect, 10, 10, 0),
+        ..Default::default()
+    })
+    .await;
+    let options = mock_options();
+    let kakao = Kakao::new(
+        server.url(),
+        server.keyword_url(),
+        "wrong-key".to_string(),
+        &options,
+    )
+    .unwrap();
+    let queries = [Query::Category(Category::Restaurant)];
+    let result = crawl(Arc::new(kakao), &queries, &Area::Rect(rect), &options).await;
+    assert!(matches!(result, Err(error::Error::ApiUnauthorized(..))));
+}
+
+#[tokio::test]
+async fn test_crawl_resumes_from_checkpoint() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let config = || mock::MockConfig {
+        places: mock::grid(Category::Restaurant, &rect, 30, 20, 0),
+        ..Default::default()
+    };
+    let restaurant = [Category::Restaurant];
+    let server = mock::MockKakao::start(config()).await;
+    crawl_mock(&server, &restaurant, &rect, &mock_options())
+        .await
+        .unwrap();
+    let uninterrupted = server.requests();
+
+    let path = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::new_v4()));
+    let options = |daily_limit, resume| CrawlOptions {
+        daily_limit,
+        concurrency: 1,
+        checkpoint: Some(path.clone()),
+        checkpoint_interval: Duration::ZERO,
+        resume,
+        ..mock_options()
+    };
+    let server = mock::MockKakao::start(config()).await;
+    // The budget runs out halfway, the progress up to there is saved.
+    let result = crawl_mock(&server, &restaurant, &rect, &options(Some(20), false)).await;
+    assert!(matches!(result, Err(error::Error::ApiQuotaExceeded(..))));
+    let saved = Checkpoint::load(&path).unwrap();
+    assert_eq!(saved.requests, 20);
+    assert!(!saved.pending.is_empty());
+
+    let result = crawl_mock(&server, &restaurant, &rect, &options(None, false)).await;
+    assert!(matches!(result, Err(error::Error::InvalidArgument(..))));
+    let result = crawl_mock(&server, &[Category::Cafe], &rect, &options(None, true)).await;
+    assert!(matches!(result, Err(error::Error::InvalidArgument(..))));
+
+    let result = crawl_mock(&server, &restaurant, &rect, &options(None, true))
+        .await
+        .unwrap();
+    assert_eq!(result.documents.len(), 600);
+    assert_eq!(server.requests(), uninterrupted);
+    assert_eq!(result.requests, uninterrupted);
+    Checkpoint::remove(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_crawl_archive_replay() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let mut places = mock::grid(Category::Restaurant, &rect, 20, 20, 0);
+    places.extend(mock::grid(Category::Cafe, &rect, 5, 5, 1000));
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places,
+        ..Default::default()
+    })
+    .await;
+    let path = std::env::temp_dir().join(format!("{}.jsonl.gz", uuid::Uuid::new_v4()));
+    let options = CrawlOptions {
+        archive: Some(path.clone()),
+        ..mock_options()
+    };
+
+    // A second run appends to the archive rather than replacing it.
+    let groups = [Category::Restaurant, Category::Cafe];
+    let first = crawl_mock(&server, &groups, &rect, &options).await.unwrap();
+    let second = crawl_mock(&server, &groups[1..], &rect, &options)
+        .await
+        .unwrap();
+    let records = archive::read(&path).unwrap();
+    assert_eq!(records.len(), first.requests + second.requests);
+    assert!(records.iter().all(|r| r.size == options.page_size));
+
+    let requests = server.requests();
+    let replayed = archive::replay(records);
+    assert_eq!(server.requests(), requests);
+    assert_eq!(replayed.len(), 425);
+    assert!(replayed.iter().all(|d| first.documents.contains(d)));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_crawl_result_fail() {
+    let rect = Rect::from_corners(126.91, 37.57, 126.92, 37.58).unwrap();
+    let mut result = CrawlResult {
+        requests: 4,
+        ..Default::default()
+    };
+    let query = Query::Category(Category::Cafe);
+    let failed = error::Error::ApiStatus(500, "internal".to_string());
+    assert!(result.fail(&query, &rect, 1, failed).is_ok());
+    assert_eq!(result.failure_ratio(), 0.25);
+
+    let unauthorized = error::Error::ApiUnauthorized("wrong key".to_string());
+    assert!(result.fail(&query, &rect, 2, unauthorized).is_err());
+    assert_eq!(result.failed.len(), 1);
+}
+
+#[test]
+fn test_category_from_str() {
+    let category: Category = "ce7".parse().unwrap();
+    assert_eq!(category, Category::Cafe);
+    assert_eq!(category.code(), "CE7");
+    assert!("FD7".parse::<Category>().is_err());
+}
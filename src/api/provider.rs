@@ -0,0 +1,18 @@
+use std::future::Future;
+
+use super::{dto::ResponseBody, Query};
+use crate::{error, types::Rect};
+
+/// A source of places that answers Kakao style paged rectangle queries.
+/// The crawler only talks to this trait, so anything that can serve
+/// `ResponseBody` pages can be crawled.
+pub trait PlaceProvider: Send + Sync + 'static {
+    /// Fetches one page of `size` documents matching `query` inside `rect`.
+    fn fetch(
+        &self,
+        query: &Query,
+        rect: &Rect,
+        page: usize,
+        size: usize,
+    ) -> impl Future<Output = Result<ResponseBody, error::Error>> + Send;
+}
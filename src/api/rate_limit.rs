@@ -0,0 +1,65 @@
+use tokio::{
+    sync::Mutex,
+    time::{sleep_until, Duration, Instant},
+};
+
+use crate::error;
+
+/// Spaces requests evenly to stay under a requests-per-second limit, and
+/// optionally stops handing out permits once a daily budget is spent.
+pub struct RateLimiter {
+    interval: Duration,
+    state: Mutex<State>,
+    daily_limit: Option<usize>,
+}
+
+struct State {
+    next: Instant,
+    issued: usize,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, daily_limit: Option<usize>) -> RateLimiter {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            state: Mutex::new(State {
+                next: Instant::now(),
+                issued: 0,
+            }),
+            daily_limit,
+        }
+    }
+
+    /// Waits until the next request may be sent.
+    pub async fn acquire(&self) -> Result<(), error::Error> {
+        let mut state = self.state.lock().await;
+        if let Some(limit) = self.daily_limit {
+            if state.issued >= limit {
+                return Err(error::Error::ApiQuotaExceeded(format!(
+                    "daily budget of {limit} requests spent"
+                )));
+            }
+        }
+        let now = Instant::now();
+        if state.next > now {
+            sleep_until(state.next).await;
+        }
+        state.next = state.next.max(now) + self.interval;
+        state.issued += 1;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_rate_limiter() {
+    let limiter = RateLimiter::new(100.0, Some(5));
+    let started = Instant::now();
+    for _ in 0..5 {
+        limiter.acquire().await.unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(40));
+    assert!(matches!(
+        limiter.acquire().await,
+        Err(error::Error::ApiQuotaExceeded(..))
+    ));
+}
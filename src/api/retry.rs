@@ -0,0 +1,78 @@
+use rand::Rng;
+use tokio::time::Duration;
+
+use crate::error;
+
+/// Exponential backoff with full jitter, capped at `max_delay`.
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (starting at 0). A `Retry-After`
+    /// sent by the server is used as the lower bound. `None` when it asks
+    /// for more than `max_delay`, e.g. until the daily quota resets, which is
+    /// not worth holding a worker for.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let jittered = ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0));
+        match retry_after {
+            Some(d) if d > self.max_delay => None,
+            Some(d) => Some(jittered.max(d)),
+            None => Some(jittered),
+        }
+    }
+
+    /// Timeouts, connection failures, 429 and 5xx responses are worth retrying.
+    pub fn is_retryable(e: &error::Error) -> bool {
+        match e {
+            error::Error::ApiRequestFailed(e) => e.is_timeout() || e.is_connect(),
+            error::Error::ApiQuotaExceeded(..) => true,
+            error::Error::ApiStatus(code, ..) => (500..600).contains(code),
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn test_delay() {
+    let policy = RetryPolicy {
+        max_retries: 5,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+    };
+    for attempt in 0..10 {
+        let delay = policy.delay(attempt, None).unwrap();
+        assert!(delay <= Duration::from_millis(100 * 2u64.pow(attempt)).min(policy.max_delay));
+    }
+    let delay = policy.delay(0, Some(Duration::from_millis(800)));
+    assert!(delay.unwrap() >= Duration::from_millis(800));
+    assert_eq!(
+        policy.delay(0, Some(policy.max_delay)),
+        Some(policy.max_delay)
+    );
+    assert_eq!(policy.delay(0, Some(Duration::from_secs(3))), None);
+}
+
+#[test]
+fn test_is_retryable() {
+    assert!(RetryPolicy::is_retryable(&error::Error::ApiStatus(
+        503,
+        String::new()
+    )));
+    assert!(RetryPolicy::is_retryable(&error::Error::ApiQuotaExceeded(
+        String::new()
+    )));
+    assert!(!RetryPolicy::is_retryable(&error::Error::ApiStatus(
+        400,
+        String::new()
+    )));
+    assert!(!RetryPolicy::is_retryable(&error::Error::ApiUnauthorized(
+        String::new()
+    )));
+}
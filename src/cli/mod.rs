@@ -0,0 +1,377 @@
+use std::path::PathBuf;
+
+use clap::{error::ErrorKind, Args, Parser, Subcommand};
+
+use crate::{
+    api::Category,
+    error,
+    export::Format,
+    types::{Coords, Rect},
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "api-data-sync", about = "Sync places from the Kakao local API")]
+pub struct Cli {
+    /// TOML config file, defaults to api-data-sync.toml when present
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Crawl Kakao and upsert the results into the database
+    Sync(SyncArgs),
+    /// Crawl Kakao and diff the result against the database, without writing anything
+    DryRun(DryRunArgs),
+    /// Crawl Kakao, or read the database, and write the places of an area to a file
+    Export(ExportArgs),
+    /// Rebuild places from a response archive and write them to a file, without calling Kakao
+    Replay(ReplayArgs),
+    /// Write every category with its parent and Korean and English names as JSON
+    Catalog(CatalogArgs),
+    /// Print row counts of the database
+    Stats,
+    /// Create or upgrade the database schema
+    Migrate,
+}
+
+/// Flags left out fall back to the config file, then the built-in defaults.
+#[derive(Args, Debug, Default)]
+pub struct CrawlArgs {
+    /// Bounding box as two opposite corners: "x1,y1,x2,y2" (longitude, latitude)
+    #[arg(long, value_parser = parse_bbox, allow_hyphen_values = true)]
+    pub bbox: Option<Rect>,
+
+    /// Center of a circular area as "x,y" (longitude, latitude), used with --radius
+    #[arg(long, value_parser = parse_center, allow_hyphen_values = true, requires = "radius", conflicts_with = "bbox")]
+    pub center: Option<Coords>,
+
+    /// Radius in meters of the circular area around --center
+    #[arg(long, value_parser = parse_positive_f64, requires = "center")]
+    pub radius: Option<f64>,
+
+    /// GeoJSON file with a Polygon or MultiPolygon, as a geometry, Feature or FeatureCollection
+    #[arg(long, conflicts_with_all = ["bbox", "center"])]
+    pub geojson: Option<PathBuf>,
+
+    /// Named area from the areas table of the config file
+    #[arg(long, conflicts_with_all = ["bbox", "center", "geojson"])]
+    pub area: Option<String>,
+
+    /// Kakao category group codes to crawl, e.g. FD6,CE7,PM9,CS2,SW8 [default: FD6,CE7]
+    #[arg(long = "group", value_parser = parse_group, value_delimiter = ',')]
+    pub groups: Vec<Category>,
+
+    /// Search term crawled through the keyword endpoint as well, repeatable
+    #[arg(long = "keyword")]
+    pub keywords: Vec<String>,
+
+    /// Documents requested per page (1 to 15) [default: 15]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=15))]
+    pub page_size: Option<u8>,
+
+    /// Cells whose longer side is at most this many meters are not split further [default: 50]
+    #[arg(long)]
+    pub min_cell_size: Option<f64>,
+
+    /// Share of failed Kakao requests (0 to 1) tolerated before the run is aborted [default: 0.05]
+    #[arg(long, value_parser = parse_ratio)]
+    pub max_failure_ratio: Option<f64>,
+
+    /// Kakao requests sent per second at most [default: 10]
+    #[arg(long, value_parser = parse_positive_f64)]
+    pub requests_per_second: Option<f64>,
+
+    /// Kakao requests this run may spend in total
+    #[arg(long)]
+    pub daily_limit: Option<usize>,
+
+    /// Retries for timeouts, 429 and 5xx responses [default: 5]
+    #[arg(long)]
+    pub max_retries: Option<u32>,
+
+    /// Seconds before a Kakao request times out [default: 10]
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Kakao requests in flight at once [default: 4]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: Option<u16>,
+
+    /// File the crawl progress is saved to, so an interrupted crawl can be resumed
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Continue the crawl saved in the checkpoint file
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Append every raw Kakao response to this gzip compressed JSON lines file
+    #[arg(long)]
+    pub archive: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub categories: CategoryArgs,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct CategoryArgs {
+    /// TOML file mapping Kakao category labels to categories, on top of the built-in table
+    #[arg(long)]
+    pub category_map: Option<PathBuf>,
+
+    /// Write the Kakao category labels no mapping knew, with counts and example places, as JSON
+    #[arg(long)]
+    pub unmapped_report: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    #[command(flatten)]
+    pub crawl: CrawlArgs,
+
+    /// Rows per multi-row statement, at most 3449 [default: 500]
+    #[arg(long, value_parser = parse_positive)]
+    pub batch_size: Option<usize>,
+
+    /// Restaurants per transaction, everything in one transaction when left out
+    #[arg(long, value_parser = parse_positive)]
+    pub transaction_size: Option<usize>,
+
+    /// Complete crawls in a row a place may be missing from before it is soft deleted [default: 3]
+    #[arg(long, value_parser = parse_positive)]
+    pub max_misses: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct DryRunArgs {
+    #[command(flatten)]
+    pub crawl: CrawlArgs,
+
+    /// Also write the diff as JSON to this file, "-" for stdout
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub crawl: CrawlArgs,
+
+    /// File to write to, "-" for stdout
+    #[arg(long, short, default_value = "-")]
+    pub output: PathBuf,
+
+    /// ndjson, csv or geojson [default: from the output extension, else ndjson]
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<Format>,
+
+    /// Export the places stored in the database instead of crawling Kakao
+    #[arg(long)]
+    pub from_db: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Archive written by a crawl with --archive
+    pub archive: PathBuf,
+
+    #[command(flatten)]
+    pub categories: CategoryArgs,
+
+    /// File to write to, "-" for stdout
+    #[arg(long, short, default_value = "-")]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct CatalogArgs {
+    /// File to write to, "-" for stdout
+    #[arg(long, short, default_value = "-")]
+    pub output: PathBuf,
+}
+
+impl Cli {
+    /// Parses the process arguments. Help and version requests print and exit,
+    /// any other parse failure is returned as `InvalidArgument` with the usage message.
+    pub fn from_env() -> Result<Cli, error::Error> {
+        Self::from_args(std::env::args_os())
+    }
+
+    pub fn from_args<I, T>(args: I) -> Result<Cli, error::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        match Cli::try_parse_from(args) {
+            Ok(cli) => Ok(cli),
+            Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+                e.exit()
+            }
+            Err(e) => Err(error::Error::InvalidArgument(e.render().to_string())),
+        }
+    }
+}
+
+fn parse_bbox(s: &str) -> Result<Rect, String> {
+    let values: Vec<f64> = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("{e} in \"{s}\""))?;
+    match values[..] {
+        [x1, y1, x2, y2] => Rect::from_corners(x1, y1, x2, y2).map_err(|e| format!("{e}")),
+        _ => Err(format!(
+            "expected 4 comma separated numbers, got {}",
+            values.len()
+        )),
+    }
+}
+
+fn parse_center(s: &str) -> Result<Coords, String> {
+    let values: Vec<f64> = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("{e} in \"{s}\""))?;
+    match values[..] {
+        [x, y] => Coords::new(x, y).map_err(|e| format!("{e}")),
+        _ => Err(format!(
+            "expected 2 comma separated numbers, got {}",
+            values.len()
+        )),
+    }
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        Ok(v) => Err(format!("{v} is not between 0 and 1")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_positive_f64(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(v) => Err(format!("{v} is not positive")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_positive(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(v) => Ok(v),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_group(s: &str) -> Result<Category, String> {
+    s.parse().map_err(|e| format!("{e}"))
+}
+
+fn parse_format(s: &str) -> Result<Format, String> {
+    s.parse().map_err(|e| format!("{e}"))
+}
+
+#[test]
+fn test_parse_sync() {
+    let cli = Cli::from_args([
+        "api-data-sync",
+        "sync",
+        "--bbox",
+        "126.93,37.58,126.91,37.57",
+        "--group",
+        "fd6,PM9",
+        "--batch-size",
+        "100",
+    ])
+    .unwrap();
+    match cli.command {
+        Command::Sync(args) => {
+            let bbox = args.crawl.bbox.unwrap();
+            assert_eq!((bbox.sw.x, bbox.sw.y), (126.91, 37.57));
+            assert_eq!(
+                args.crawl.groups,
+                vec![Category::Restaurant, Category::Pharmacy]
+            );
+            assert_eq!(args.crawl.page_size, None);
+            assert_eq!(args.batch_size, Some(100));
+        }
+        c => panic!("unexpected command {c:?}"),
+    }
+}
+
+#[test]
+fn test_parse_invalid() {
+    let invalid = [
+        vec![
+            "api-data-sync",
+            "sync",
+            "--bbox",
+            "126.91,37.57,126.93,37.58",
+            "--area",
+            "hongdae",
+        ],
+        vec!["api-data-sync", "sync", "--bbox", "126.91,37.57,126.93"],
+        vec!["api-data-sync", "sync", "--center", "126.9237,37.5563"],
+        vec![
+            "api-data-sync",
+            "sync",
+            "--center",
+            "126.9237,37.5563",
+            "--radius",
+            "0",
+        ],
+        vec!["api-data-sync", "sync", "--bbox", "126.91,37.57,126.93,abc"],
+        vec![
+            "api-data-sync",
+            "sync",
+            "--bbox",
+            "126.91,97.57,126.93,37.58",
+        ],
+        vec![
+            "api-data-sync",
+            "sync",
+            "--bbox",
+            "126.91,37.57,126.93,37.58",
+            "--group",
+            "XX1",
+        ],
+        vec![
+            "api-data-sync",
+            "dry-run",
+            "--bbox",
+            "126.91,37.57,126.93,37.58",
+            "--page-size",
+            "16",
+        ],
+        vec![
+            "api-data-sync",
+            "sync",
+            "--bbox",
+            "126.91,37.57,126.93,37.58",
+            "--max-failure-ratio",
+            "1.5",
+        ],
+        vec![
+            "api-data-sync",
+            "sync",
+            "--bbox",
+            "126.91,37.57,126.93,37.58",
+            "--requests-per-second",
+            "0",
+        ],
+        vec!["api-data-sync", "unknown"],
+    ];
+    for args in invalid {
+        assert!(matches!(
+            Cli::from_args(args),
+            Err(error::Error::InvalidArgument(..))
+        ));
+    }
+}
@@ -0,0 +1,549 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    api::{Category, CrawlOptions},
+    cli::{CategoryArgs, CrawlArgs, SyncArgs},
+    db, error,
+    types::{Area, CategoryMap, Circle, Coords, Polygon, Rect},
+    utils::Const,
+};
+
+/// Read when no `--config` is given and `API_DATA_SYNC_CONFIG` is not set.
+const DEFAULT_CONFIG_FILE: &str = "api-data-sync.toml";
+
+/// Settings are layered: built-in defaults, then the TOML config file, then
+/// environment variables, then command line flags.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub kakao: KakaoConfig,
+    pub db: DbConfig,
+    pub crawl: CrawlConfig,
+    pub categories: CategoryConfig,
+    /// Named areas selected with `--area`.
+    pub areas: HashMap<String, AreaConfig>,
+}
+
+/// A bounding box as `[x1, y1, x2, y2]`, a circle as
+/// `{ center = [x, y], radius = meters }`, or a GeoJSON Polygon or
+/// MultiPolygon file as `{ geojson = "path" }`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AreaConfig {
+    Rect([f64; 4]),
+    Circle { center: [f64; 2], radius: f64 },
+    GeoJson { geojson: PathBuf },
+}
+
+impl AreaConfig {
+    pub fn to_area(&self) -> Result<Area, error::Error> {
+        match self {
+            Self::Rect([x1, y1, x2, y2]) => Ok(Area::Rect(Rect::from_corners(*x1, *y1, *x2, *y2)?)),
+            Self::Circle {
+                center: [x, y],
+                radius,
+            } => Ok(Area::Circle(Circle::new(Coords::new(*x, *y)?, *radius)?)),
+            Self::GeoJson { geojson } => Ok(Area::Polygon(Polygon::from_file(geojson)?)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct KakaoConfig {
+    pub api_key: Option<String>,
+    pub api_url: String,
+    pub keyword_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbConfig {
+    pub url: Option<String>,
+    pub batch_size: usize,
+    pub transaction_size: Option<usize>,
+    /// Complete crawls in a row a place may be missing from before it is soft deleted.
+    pub max_misses: usize,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrawlConfig {
+    /// Area from `areas` crawled when neither `--bbox` nor `--area` is given.
+    pub default_area: Option<String>,
+    pub groups: Vec<String>,
+    /// Search terms crawled through the keyword endpoint on top of `groups`.
+    pub keywords: Vec<String>,
+    pub page_size: usize,
+    pub min_cell_size: f64,
+    pub max_failure_ratio: f64,
+    pub requests_per_second: f64,
+    pub daily_limit: Option<usize>,
+    pub max_retries: u32,
+    /// Seconds
+    pub timeout: u64,
+    pub concurrency: usize,
+    /// File the crawl progress is saved to, so an interrupted crawl can be resumed.
+    pub checkpoint: Option<PathBuf>,
+    /// Seconds between checkpoint saves
+    pub checkpoint_interval: u64,
+    /// Set by `--resume` only.
+    #[serde(skip)]
+    pub resume: bool,
+    /// Gzip compressed JSON lines file the raw responses are appended to, for `replay`.
+    pub archive: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CategoryConfig {
+    /// TOML file of Kakao labels to category names, on top of the built-in table.
+    pub mapping: Option<PathBuf>,
+    /// JSON file the labels no mapping knew are written to after each crawl.
+    pub unmapped_report: Option<PathBuf>,
+}
+
+impl Default for KakaoConfig {
+    fn default() -> Self {
+        KakaoConfig {
+            api_key: None,
+            api_url: "https://dapi.kakao.com/v2/local/search/category.json".to_string(),
+            keyword_url: "https://dapi.kakao.com/v2/local/search/keyword.json".to_string(),
+        }
+    }
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        DbConfig {
+            url: None,
+            batch_size: 500,
+            transaction_size: None,
+            max_misses: 3,
+        }
+    }
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        let options = CrawlOptions::default();
+        CrawlConfig {
+            default_area: None,
+            groups: vec!["FD6".to_string(), "CE7".to_string()],
+            keywords: Vec::new(),
+            page_size: options.page_size,
+            min_cell_size: options.min_cell_size,
+            max_failure_ratio: options.max_failure_ratio,
+            requests_per_second: options.requests_per_second,
+            daily_limit: options.daily_limit,
+            max_retries: options.max_retries,
+            timeout: options.timeout.as_secs(),
+            concurrency: options.concurrency,
+            checkpoint: options.checkpoint,
+            checkpoint_interval: options.checkpoint_interval.as_secs(),
+            resume: options.resume,
+            archive: options.archive,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file and applies environment overrides. A missing
+    /// default file is fine, a missing file passed explicitly is not.
+    pub fn load(path: Option<&Path>) -> Result<Config, error::Error> {
+        let env_path = Const::ConfigFile.optional_value();
+        let path = path.or(env_path.as_deref().map(Path::new));
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, error::Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        Self::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, error::Error> {
+        match toml::from_str(text) {
+            Ok(config) => Ok(config),
+            Err(e) => Err(error::Error::InvalidConfig(e.message().to_string())),
+        }
+    }
+
+    fn apply_env(&mut self) -> Result<(), error::Error> {
+        if let Some(v) = Const::KakaoRestApiKey.optional_value() {
+            self.kakao.api_key = Some(v);
+        }
+        if let Some(v) = Const::KakaoRestApiUrl.optional_value() {
+            self.kakao.api_url = v;
+        }
+        if let Some(v) = Const::KakaoKeywordApiUrl.optional_value() {
+            self.kakao.keyword_url = v;
+        }
+        if let Some(v) = Const::DbUrl.optional_value() {
+            self.db.url = Some(v);
+        }
+        if let Some(v) = env_parsed(Const::DbBatchSize)? {
+            self.db.batch_size = v;
+        }
+        if let Some(v) = env_parsed(Const::DbTransactionSize)? {
+            self.db.transaction_size = Some(v);
+        }
+        if let Some(v) = Const::CrawlGroups.optional_value() {
+            self.crawl.groups = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Some(v) = Const::CrawlKeywords.optional_value() {
+            self.crawl.keywords = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Some(v) = env_parsed(Const::CrawlRequestsPerSecond)? {
+            self.crawl.requests_per_second = v;
+        }
+        if let Some(v) = env_parsed(Const::CrawlDailyLimit)? {
+            self.crawl.daily_limit = Some(v);
+        }
+        if let Some(v) = env_parsed(Const::CrawlConcurrency)? {
+            self.crawl.concurrency = v;
+        }
+        if let Some(v) = Const::CategoryMap.optional_value() {
+            self.categories.mapping = Some(PathBuf::from(v));
+        }
+        Ok(())
+    }
+
+    /// Applies the crawl flags given on the command line.
+    pub fn apply_args(&mut self, args: &CrawlArgs) {
+        self.apply_category_args(&args.categories);
+        if !args.groups.is_empty() {
+            self.crawl.groups = args.groups.iter().map(|c| c.code()).collect();
+        }
+        if !args.keywords.is_empty() {
+            self.crawl.keywords = args.keywords.clone();
+        }
+        let crawl = &mut self.crawl;
+        if let Some(v) = args.page_size {
+            crawl.page_size = v as usize;
+        }
+        if let Some(v) = args.min_cell_size {
+            crawl.min_cell_size = v;
+        }
+        if let Some(v) = args.max_failure_ratio {
+            crawl.max_failure_ratio = v;
+        }
+        if let Some(v) = args.requests_per_second {
+            crawl.requests_per_second = v;
+        }
+        if let Some(v) = args.daily_limit {
+            crawl.daily_limit = Some(v);
+        }
+        if let Some(v) = args.max_retries {
+            crawl.max_retries = v;
+        }
+        if let Some(v) = args.timeout {
+            crawl.timeout = v;
+        }
+        if let Some(v) = args.concurrency {
+            crawl.concurrency = v as usize;
+        }
+        if let Some(v) = args.checkpoint.as_ref() {
+            crawl.checkpoint = Some(v.clone());
+        }
+        crawl.resume = args.resume;
+        if let Some(v) = args.archive.as_ref() {
+            crawl.archive = Some(v.clone());
+        }
+    }
+
+    pub fn apply_category_args(&mut self, args: &CategoryArgs) {
+        if let Some(v) = args.category_map.as_ref() {
+            self.categories.mapping = Some(v.clone());
+        }
+        if let Some(v) = args.unmapped_report.as_ref() {
+            self.categories.unmapped_report = Some(v.clone());
+        }
+    }
+
+    pub fn apply_sync_args(&mut self, args: &SyncArgs) {
+        self.apply_args(&args.crawl);
+        if let Some(v) = args.batch_size {
+            self.db.batch_size = v;
+        }
+        if let Some(v) = args.transaction_size {
+            self.db.transaction_size = Some(v);
+        }
+        if let Some(v) = args.max_misses {
+            self.db.max_misses = v;
+        }
+    }
+
+    /// Checks the settings a crawl needs.
+    pub fn validate_crawl(&self) -> Result<(), error::Error> {
+        self.api_key()?;
+        let crawl = &self.crawl;
+        if self.groups()?.is_empty() && crawl.keywords.is_empty() {
+            return invalid("crawl.groups and crawl.keywords are both empty".to_string());
+        }
+        if crawl.keywords.iter().any(|k| k.trim().is_empty()) {
+            return invalid("crawl.keywords has an empty keyword".to_string());
+        }
+        if crawl.resume && crawl.checkpoint.is_none() {
+            return invalid("--resume needs --checkpoint or crawl.checkpoint".to_string());
+        }
+        if !(1..=15).contains(&crawl.page_size) {
+            return invalid(format!(
+                "crawl.page_size {} is not between 1 and 15",
+                crawl.page_size
+            ));
+        }
+        if crawl.min_cell_size.is_nan() || crawl.min_cell_size <= 0.0 {
+            return invalid(format!(
+                "crawl.min_cell_size {} is not positive",
+                crawl.min_cell_size
+            ));
+        }
+        if !(0.0..=1.0).contains(&crawl.max_failure_ratio) {
+            return invalid(format!(
+                "crawl.max_failure_ratio {} is not between 0 and 1",
+                crawl.max_failure_ratio
+            ));
+        }
+        if !(crawl.requests_per_second > 0.0 && crawl.requests_per_second.is_finite()) {
+            return invalid(format!(
+                "crawl.requests_per_second {} is not a positive rate",
+                crawl.requests_per_second
+            ));
+        }
+        if crawl.concurrency == 0 {
+            return invalid("crawl.concurrency must be at least 1".to_string());
+        }
+        if crawl.timeout == 0 {
+            return invalid("crawl.timeout must be at least 1 second".to_string());
+        }
+        for (name, area) in self.areas.iter() {
+            if let Err(e) = area.to_area().and_then(|a| a.bounds()) {
+                return invalid(format!("areas.{name} is not a valid area: {e}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the settings a database connection needs.
+    pub fn validate_db(&self) -> Result<(), error::Error> {
+        self.db_url()?;
+        if !(1..=db::MAX_BATCH_SIZE).contains(&self.db.batch_size) {
+            return invalid(format!(
+                "db.batch_size {} is not between 1 and {}",
+                self.db.batch_size,
+                db::MAX_BATCH_SIZE
+            ));
+        }
+        if self.db.transaction_size == Some(0) {
+            return invalid("db.transaction_size must be at least 1".to_string());
+        }
+        if self.db.max_misses == 0 {
+            return invalid("db.max_misses must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn api_key(&self) -> Result<&str, error::Error> {
+        match self.kakao.api_key.as_deref() {
+            Some(key) if !key.trim().is_empty() => Ok(key),
+            _ => Err(error::Error::MissingConfig(format!(
+                "kakao.api_key or {}",
+                Const::KakaoRestApiKey.key()
+            ))),
+        }
+    }
+
+    pub fn db_url(&self) -> Result<&str, error::Error> {
+        match self.db.url.as_deref() {
+            Some(url) if !url.trim().is_empty() => Ok(url),
+            _ => Err(error::Error::MissingConfig(format!(
+                "db.url or {}",
+                Const::DbUrl.key()
+            ))),
+        }
+    }
+
+    pub fn groups(&self) -> Result<Vec<Category>, error::Error> {
+        let mut groups = Vec::new();
+        for code in self.crawl.groups.iter() {
+            let category = match Category::from_str(code) {
+                Ok(c) => c,
+                Err(..) => return invalid(format!("crawl.groups has unknown code {code}")),
+            };
+            if !groups.contains(&category) {
+                groups.push(category);
+            }
+        }
+        Ok(groups)
+    }
+
+    /// The `--bbox` flag, else the `--area` flag, else `crawl.default_area`.
+    pub fn area(&self, args: &CrawlArgs) -> Result<Area, error::Error> {
+        if let Some(rect) = args.bbox.as_ref() {
+            return Ok(Area::Rect(rect.clone()));
+        }
+        if let (Some(center), Some(radius)) = (args.center.as_ref(), args.radius) {
+            return Ok(Area::Circle(Circle::new(center.clone(), radius)?));
+        }
+        if let Some(path) = args.geojson.as_ref() {
+            return Ok(Area::Polygon(Polygon::from_file(path)?));
+        }
+        let name =
+            match args.area.as_ref().or(self.crawl.default_area.as_ref()) {
+                Some(name) => name,
+                None => return Err(error::Error::MissingConfig(
+                    "area, pass --bbox, --center, --geojson or --area or set crawl.default_area"
+                        .to_string(),
+                )),
+            };
+        match self.areas.get(name) {
+            Some(area) => area.to_area(),
+            None => invalid(format!("area {name} is not defined in areas")),
+        }
+    }
+
+    /// The built-in label mapping, extended by `categories.mapping` when set.
+    pub fn category_map(&self) -> Result<CategoryMap, error::Error> {
+        match self.categories.mapping.as_ref() {
+            Some(path) => CategoryMap::from_file(path),
+            None => Ok(CategoryMap::default()),
+        }
+    }
+
+    pub fn crawl_options(&self) -> CrawlOptions {
+        let crawl = &self.crawl;
+        CrawlOptions {
+            page_size: crawl.page_size,
+            min_cell_size: crawl.min_cell_size,
+            max_failure_ratio: crawl.max_failure_ratio,
+            requests_per_second: crawl.requests_per_second,
+            daily_limit: crawl.daily_limit,
+            max_retries: crawl.max_retries,
+            timeout: Duration::from_secs(crawl.timeout),
+            concurrency: crawl.concurrency,
+            checkpoint: crawl.checkpoint.clone(),
+            checkpoint_interval: Duration::from_secs(crawl.checkpoint_interval),
+            resume: crawl.resume,
+            archive: crawl.archive.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+fn invalid<T>(msg: String) -> Result<T, error::Error> {
+    Err(error::Error::InvalidConfig(msg))
+}
+
+fn env_parsed<T: FromStr>(c: Const) -> Result<Option<T>, error::Error> {
+    match c.optional_value() {
+        Some(v) => match v.parse() {
+            Ok(v) => Ok(Some(v)),
+            Err(..) => invalid(format!("{} has invalid value {v}", c.key())),
+        },
+        None => Ok(None),
+    }
+}
+
+#[test]
+fn test_from_toml() {
+    let config = Config::from_toml(
+        r#"
+        [kakao]
+        api_key = "some-key"
+
+        [db]
+        batch_size = 100
+
+        [crawl]
+        default_area = "hongdae"
+        groups = ["FD6"]
+        requests_per_second = 5.0
+
+        [areas]
+        hongdae = [126.93, 37.55, 126.91, 37.56]
+        hongik_station = { center = [126.9237, 37.5563], radius = 500.0 }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.api_key().unwrap(), "some-key");
+    assert_eq!(config.db.batch_size, 100);
+    assert_eq!(config.db.transaction_size, None);
+    assert_eq!(config.db.max_misses, 3);
+    assert_eq!(config.groups().unwrap(), vec![Category::Restaurant]);
+    assert_eq!(config.crawl.page_size, 15);
+    assert_eq!(config.crawl_options().requests_per_second, 5.0);
+    assert!(config.validate_crawl().is_ok());
+    let args = CrawlArgs::default();
+    assert!(matches!(config.area(&args), Ok(Area::Rect(..))));
+    let args = CrawlArgs {
+        area: Some("hongik_station".to_string()),
+        ..Default::default()
+    };
+    match config.area(&args).unwrap() {
+        Area::Circle(c) => assert_eq!(c.radius, 500.0),
+        a => panic!("unexpected area {a:?}"),
+    }
+    assert!(matches!(
+        config.validate_db(),
+        Err(error::Error::MissingConfig(..))
+    ));
+}
+
+#[test]
+fn test_invalid_config() {
+    let invalid = [
+        "[crawl]\npage_size = 20",
+        "[crawl]\ngroups = []",
+        "[crawl]\ngroups = [\"XX1\"]",
+        "[crawl]\nmax_failure_ratio = 2.0",
+        "[crawl]\nconcurrency = 0",
+        "[crawl]\ntimeout = 0",
+        "[areas]\nsomewhere = [126.91, 97.0, 126.93, 37.56]",
+        "[areas]\nsomewhere = { center = [126.91, 37.56], radius = -1.0 }",
+    ];
+    for text in invalid {
+        let mut config = Config::from_toml(text).unwrap();
+        config.kakao.api_key = Some("some-key".to_string());
+        assert!(
+            matches!(
+                config.validate_crawl(),
+                Err(error::Error::InvalidConfig(..))
+            ),
+            "{text}"
+        );
+    }
+    for text in ["[db]\nbatch_size = 0", "[db]\nbatch_size = 10000"] {
+        let mut config = Config::from_toml(text).unwrap();
+        config.db.url = Some("mysql://localhost/test".to_string());
+        assert!(
+            matches!(config.validate_db(), Err(error::Error::InvalidConfig(..))),
+            "{text}"
+        );
+    }
+    assert!(matches!(
+        Config::from_toml("[crawl]\nunknown = 1"),
+        Err(error::Error::InvalidConfig(..))
+    ));
+    assert!(matches!(
+        Config::default().validate_crawl(),
+        Err(error::Error::MissingConfig(..))
+    ));
+}
@@ -1,71 +1,681 @@
 pub mod models;
 
-use sqlx::MySqlPool;
+use std::collections::{HashMap, HashSet};
 
-use crate::{error, utils::*};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    MySql, MySqlConnection, MySqlPool, QueryBuilder, Transaction,
+};
 
-use self::models::{Category, Restaurant};
+use crate::{api, error, types::Area};
+
+use self::models::{Category, Place, Restaurant};
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Placeholders MySQL accepts in a single statement.
+const MAX_BINDS: usize = 65535;
+
+const RESTAURANT_COLUMNS: &str = "id, name, address, road_address, phone, place_url, x, y, kakao_place_id, category_group_code, category_name, primary_category, api_called_at, scraped_at, created_at, updated_at, missing_since, miss_count, deleted_at";
+
+/// Columns bound per row by the widest multi-row statement, the restaurant upsert.
+const RESTAURANT_BINDS: usize = 19;
+
+/// The largest batch whose restaurant upsert stays within `MAX_BINDS`.
+pub const MAX_BATCH_SIZE: usize = MAX_BINDS / RESTAURANT_BINDS;
+
+/// The schema the `db::models` structs map to, from the `migrations` directory.
+static MIGRATOR: Migrator = sqlx::migrate!();
 
 pub struct DbPool {
     pool: MySqlPool,
+    batch_size: usize,
+    transaction_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WriteSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub categories_inserted: usize,
+    /// Kept categories whose source or confidence changed.
+    pub categories_updated: usize,
+    pub categories_deleted: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ReconcileSummary {
+    /// Places whose miss count went up, including the deleted ones.
+    pub marked: usize,
+    pub deleted: usize,
+}
+
+impl std::ops::AddAssign for ReconcileSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.marked += other.marked;
+        self.deleted += other.deleted;
+    }
+}
+
+#[derive(Debug)]
+pub struct Stats {
+    /// Live restaurants, and their categories.
+    pub restaurants: i64,
+    pub categories: Vec<(String, i64)>,
+    /// Live places per category group code.
+    pub places: Vec<(String, i64)>,
+}
+
+impl std::ops::AddAssign for WriteSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.categories_inserted += other.categories_inserted;
+        self.categories_updated += other.categories_updated;
+        self.categories_deleted += other.categories_deleted;
+    }
 }
 
 impl DbPool {
-    pub async fn new() -> Result<DbPool, error::Error> {
-        match MySqlPool::connect(&Const::DbUrl.value()).await {
-            Ok(pool) => Ok(DbPool { pool }),
+    pub async fn new(url: &str) -> Result<DbPool, error::Error> {
+        match MySqlPool::connect(url).await {
+            Ok(pool) => Ok(DbPool {
+                pool,
+                batch_size: DEFAULT_BATCH_SIZE,
+                transaction_size: None,
+            }),
             Err(e) => Err(error::Error::DbConnectionFailed(e)),
         }
     }
 
-    pub async fn insert_all(
+    /// Number of rows sent in a single multi-row statement.
+    pub fn with_batch_size(mut self, batch_size: usize) -> DbPool {
+        self.batch_size = batch_size.clamp(1, MAX_BATCH_SIZE);
+        self
+    }
+
+    /// Number of restaurants committed per transaction. `None` writes
+    /// everything in one transaction, so a run either fully commits or rolls back.
+    pub fn with_transaction_size(mut self, transaction_size: Option<usize>) -> DbPool {
+        self.transaction_size = transaction_size.map(|n| n.max(1));
+        self
+    }
+
+    /// Applies pending migrations and returns their descriptions.
+    pub async fn migrate(&self) -> Result<Vec<String>, error::Error> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => return Err(error::Error::DbConnectionFailed(e)),
+        };
+        let applied = match conn.ensure_migrations_table().await {
+            Ok(..) => conn.list_applied_migrations().await,
+            Err(e) => Err(e),
+        };
+        let applied: HashSet<i64> = match applied {
+            Ok(v) => v.into_iter().map(|m| m.version).collect(),
+            Err(e) => return Err(error::Error::MigrationFailed(e)),
+        };
+        let pending = MIGRATOR
+            .iter()
+            .filter(|m| !applied.contains(&m.version))
+            .map(|m| format!("{:04} {}", m.version, m.description))
+            .collect();
+        match MIGRATOR.run(&mut *conn).await {
+            Ok(..) => Ok(pending),
+            Err(e) => Err(error::Error::MigrationFailed(e)),
+        }
+    }
+
+    /// Upserts restaurants by `kakao_place_id`. Rows already stored are
+    /// looked up first and written back under their own id, so a place is
+    /// updated in place even where the unique index on `kakao_place_id` is
+    /// missing. Of several rows given for one place, the last one is written.
+    pub async fn upsert_all(
         &self,
         data: Vec<(Restaurant, Vec<Category>)>,
-    ) -> Result<(), error::Error> {
-        for d in data.into_iter() {
-            let rid = d.0.id.clone();
-            Self::insert_restaurant(d.0, &self.pool).await?;
-            for c in d.1.into_iter() {
-                Self::insert_category(c, &rid, &self.pool).await?;
+    ) -> Result<WriteSummary, error::Error> {
+        let mut seen = HashSet::new();
+        let mut data: Vec<_> = data
+            .into_iter()
+            .rev()
+            .filter(|(r, ..)| seen.insert(r.kakao_place_id.clone()))
+            .collect();
+        data.reverse();
+        let transaction_size = self.transaction_size.unwrap_or(data.len()).max(1);
+        let mut summary = WriteSummary::default();
+        for chunk in data.chunks(transaction_size) {
+            let mut tx = match self.pool.begin().await {
+                Ok(tx) => tx,
+                Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+            };
+            let mut written = WriteSummary::default();
+            for batch in chunk.chunks(self.batch_size) {
+                written += Self::upsert_batch(batch, &mut tx).await?;
+            }
+            if let Err(e) = tx.commit().await {
+                return Err(error::Error::SqlExecutionFailed(e));
+            }
+            summary += written;
+        }
+        Ok(summary)
+    }
+
+    /// Upserts places of non-food groups by `kakao_place_id`, keeping the id
+    /// and `created_at` of rows already stored. Returns the number of places written.
+    pub async fn upsert_places(&self, places: &[Place]) -> Result<usize, error::Error> {
+        let transaction_size = self.transaction_size.unwrap_or(places.len()).max(1);
+        for chunk in places.chunks(transaction_size) {
+            let mut tx = match self.pool.begin().await {
+                Ok(tx) => tx,
+                Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+            };
+            for batch in chunk.chunks(self.batch_size) {
+                Self::upsert_place_batch(batch, &mut tx).await?;
+            }
+            if let Err(e) = tx.commit().await {
+                return Err(error::Error::SqlExecutionFailed(e));
             }
         }
-        Ok(())
-    }
-
-    async fn insert_restaurant(r: Restaurant, pool: &MySqlPool) -> Result<(), error::Error> {
-        let sql = "insert into restaurant (id, name, address, x, y, kakao_place_id, api_called_at, scraped_at, created_at, updated_at) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
-        let result = sqlx::query(sql)
-            .bind(r.id)
-            .bind(r.name)
-            .bind(r.address)
-            .bind(r.x)
-            .bind(r.y)
-            .bind(r.kakao_place_id)
-            .bind(r.api_called_at)
-            .bind(r.scraped_at)
-            .bind(r.created_at)
-            .bind(r.updated_at)
-            .execute(pool)
+        Ok(places.len())
+    }
+
+    /// Live restaurants of the food groups among `groups` inside `area`, with
+    /// their categories.
+    pub async fn find_in_area(
+        &self,
+        area: &Area,
+        groups: &[api::Category],
+    ) -> Result<Vec<(Restaurant, Vec<Category>)>, error::Error> {
+        let food: Vec<&api::Category> = groups.iter().filter(|c| c.is_food()).collect();
+        if food.is_empty() {
+            return Ok(vec![]);
+        }
+        let rect = area.bounds()?;
+        let mut query = QueryBuilder::<MySql>::new("select * from restaurant where x between ");
+        query
+            .push_bind(rect.sw.x)
+            .push(" and ")
+            .push_bind(rect.ne.x)
+            .push(" and y between ")
+            .push_bind(rect.sw.y)
+            .push(" and ")
+            .push_bind(rect.ne.y)
+            .push(" and deleted_at is null");
+        push_group_filter(&mut query, &food, includes_unknown_group(groups));
+        let result = query
+            .build_query_as::<Restaurant>()
+            .fetch_all(&self.pool)
             .await;
+        let restaurants: Vec<Restaurant> = match result {
+            Ok(v) => v.into_iter().filter(|r| area.contains(r.x, r.y)).collect(),
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        };
+        let mut categories: HashMap<String, Vec<Category>> = HashMap::new();
+        let mut conn = match self.pool.acquire().await {
+            Ok(c) => c,
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        };
+        for chunk in restaurants.chunks(self.batch_size) {
+            let rids: Vec<&str> = chunk.iter().map(|r| r.id.as_str()).collect();
+            for c in Self::find_categories(&rids, &mut conn).await? {
+                categories
+                    .entry(c.restaurant_id.clone())
+                    .or_default()
+                    .push(c);
+            }
+        }
+        Ok(restaurants
+            .into_iter()
+            .map(|r| {
+                let c = categories.remove(&r.id).unwrap_or_default();
+                (r, c)
+            })
+            .collect())
+    }
+
+    /// Live places of the other category groups inside `area`.
+    pub async fn find_places_in_area(&self, area: &Area) -> Result<Vec<Place>, error::Error> {
+        let rect = area.bounds()?;
+        let result = sqlx::query_as::<_, Place>(
+            "select * from place where x between ? and ? and y between ? and ? and deleted_at is null",
+        )
+        .bind(rect.sw.x)
+        .bind(rect.ne.x)
+        .bind(rect.sw.y)
+        .bind(rect.ne.y)
+        .fetch_all(&self.pool)
+        .await;
         match result {
-            Ok(..) => Ok(()),
+            Ok(v) => Ok(v.into_iter().filter(|p| area.contains(p.x, p.y)).collect()),
             Err(e) => Err(error::Error::SqlExecutionFailed(e)),
         }
     }
 
-    async fn insert_category(c: Category, rid: &str, pool: &MySqlPool) -> Result<(), error::Error> {
-        let result = sqlx::query(
-            "insert into restaurant_categories (restaurant_id, categories) values (?, ?)",
+    /// Marks live places of `groups` inside `area` that a complete crawl did
+    /// not return as missing, and soft deletes the ones missed `max_misses` times
+    /// in a row. Food groups live in `restaurant`, the others in `place`.
+    pub async fn mark_missing(
+        &self,
+        area: &Area,
+        groups: &[api::Category],
+        seen: &HashSet<&str>,
+        max_misses: i32,
+    ) -> Result<ReconcileSummary, error::Error> {
+        let (food, other): (Vec<_>, Vec<_>) = groups.iter().partition(|c| c.is_food());
+        let include_unknown = includes_unknown_group(groups);
+
+        let rect = area.bounds()?;
+        let mut tx = match self.pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        };
+        let mut summary = ReconcileSummary::default();
+        for (table, groups, include_unknown) in [
+            ("restaurant", food, include_unknown),
+            ("place", other, false),
+        ] {
+            if groups.is_empty() {
+                continue;
+            }
+            let mut query = QueryBuilder::<MySql>::new("select id, kakao_place_id, x, y from ");
+            query
+                .push(table)
+                .push(" where x between ")
+                .push_bind(rect.sw.x)
+                .push(" and ")
+                .push_bind(rect.ne.x)
+                .push(" and y between ")
+                .push_bind(rect.sw.y)
+                .push(" and ")
+                .push_bind(rect.ne.y)
+                .push(" and deleted_at is null");
+            push_group_filter(&mut query, &groups, include_unknown);
+            let result = query
+                .build_query_as::<(String, String, f64, f64)>()
+                .fetch_all(&mut *tx)
+                .await;
+            let stored = match result {
+                Ok(v) => v,
+                Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+            };
+            let missing: Vec<&str> = stored
+                .iter()
+                .filter(|(_, kakao_place_id, x, y)| {
+                    area.contains(*x, *y) && !seen.contains(kakao_place_id.as_str())
+                })
+                .map(|(id, ..)| id.as_str())
+                .collect();
+            for chunk in missing.chunks(self.batch_size) {
+                summary += Self::mark_missing_batch(table, chunk, max_misses, &mut tx).await?;
+            }
+        }
+        match tx.commit().await {
+            Ok(..) => Ok(summary),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+
+    pub async fn stats(&self) -> Result<Stats, error::Error> {
+        let restaurants: Result<(i64,), _> =
+            sqlx::query_as("select count(*) from restaurant where deleted_at is null")
+                .fetch_one(&self.pool)
+                .await;
+        let restaurants = match restaurants {
+            Ok((n,)) => n,
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        };
+        let categories = sqlx::query_as(
+            "select c.categories, count(*) from restaurant_categories c join restaurant r on r.id = c.restaurant_id where r.deleted_at is null group by c.categories order by count(*) desc",
+        )
+        .fetch_all(&self.pool)
+        .await;
+        let categories = match categories {
+            Ok(v) => v,
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        };
+        let places = sqlx::query_as(
+            "select category_group_code, count(*) from place where deleted_at is null group by category_group_code order by count(*) desc",
         )
-        .bind(rid)
-        .bind(c.categories)
-        .execute(pool)
+        .fetch_all(&self.pool)
         .await;
+        match places {
+            Ok(places) => Ok(Stats {
+                restaurants,
+                categories,
+                places,
+            }),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+
+    async fn upsert_batch(
+        batch: &[(Restaurant, Vec<Category>)],
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<WriteSummary, error::Error> {
+        let mut summary = WriteSummary::default();
+        let existing = Self::find_by_kakao_place_ids(batch, tx).await?;
+
+        let mut restaurants = Vec::with_capacity(batch.len());
+        let mut categories = Vec::new();
+        for (r, c) in batch.iter() {
+            let r = match existing.get(&r.kakao_place_id) {
+                Some(e) => {
+                    summary.updated += 1;
+                    Restaurant {
+                        id: e.id.clone(),
+                        scraped_at: e.scraped_at,
+                        created_at: e.created_at,
+                        ..r.clone()
+                    }
+                }
+                None => {
+                    summary.inserted += 1;
+                    r.clone()
+                }
+            };
+            categories.extend(c.iter().map(|c| Category {
+                restaurant_id: r.id.clone(),
+                ..c.clone()
+            }));
+            restaurants.push(r);
+        }
+
+        Self::upsert_restaurants(&restaurants, tx).await?;
+
+        let existing_ids: Vec<&str> = existing.values().map(|r| r.id.as_str()).collect();
+        let stored = Self::find_categories(&existing_ids, tx).await?;
+        let to_delete: Vec<&Category> = stored
+            .iter()
+            .filter(|s| {
+                !categories
+                    .iter()
+                    .any(|c| c.restaurant_id == s.restaurant_id && c.categories == s.categories)
+            })
+            .collect();
+        // Kept categories whose source or confidence changed are written again.
+        let to_write: Vec<&Category> = categories
+            .iter()
+            .filter(|c| {
+                !stored.iter().any(|s| {
+                    c.restaurant_id == s.restaurant_id
+                        && c.categories == s.categories
+                        && c.source == s.source
+                        && c.confidence == s.confidence
+                })
+            })
+            .collect();
+        summary.categories_updated = to_write
+            .iter()
+            .filter(|c| {
+                stored
+                    .iter()
+                    .any(|s| c.restaurant_id == s.restaurant_id && c.categories == s.categories)
+            })
+            .count();
+        summary.categories_inserted = to_write.len() - summary.categories_updated;
+        // A restaurant can have any number of categories, so their statements
+        // are split to stay within `MAX_BINDS` on their own.
+        for chunk in to_delete.chunks(MAX_BINDS / 2) {
+            summary.categories_deleted += Self::delete_categories(chunk, tx).await?;
+        }
+        for chunk in to_write.chunks(MAX_BINDS / 4) {
+            Self::insert_categories(chunk, tx).await?;
+        }
+        Ok(summary)
+    }
+
+    async fn mark_missing_batch(
+        table: &str,
+        ids: &[&str],
+        max_misses: i32,
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<ReconcileSummary, error::Error> {
+        let now = chrono::Utc::now();
+        let mut summary = ReconcileSummary::default();
+
+        let mut query = QueryBuilder::<MySql>::new("update ");
+        query
+            .push(table)
+            .push(" set miss_count = miss_count + 1, missing_since = coalesce(missing_since, ")
+            .push_bind(now)
+            .push(") where id in (");
+        let mut separated = query.separated(", ");
+        for id in ids.iter() {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        match query.build().execute(&mut **tx).await {
+            Ok(r) => summary.marked += r.rows_affected() as usize,
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        }
+
+        let mut query = QueryBuilder::<MySql>::new("update ");
+        query
+            .push(table)
+            .push(" set deleted_at = ")
+            .push_bind(now)
+            .push(" where miss_count >= ")
+            .push_bind(max_misses)
+            .push(" and id in (");
+        let mut separated = query.separated(", ");
+        for id in ids.iter() {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        match query.build().execute(&mut **tx).await {
+            Ok(r) => summary.deleted += r.rows_affected() as usize,
+            Err(e) => return Err(error::Error::SqlExecutionFailed(e)),
+        }
+        Ok(summary)
+    }
+
+    /// Stored restaurants by `kakao_place_id`. Where a place was stored more
+    /// than once, the oldest row wins, as in the 0004 migration.
+    async fn find_by_kakao_place_ids(
+        batch: &[(Restaurant, Vec<Category>)],
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<HashMap<String, Restaurant>, error::Error> {
+        if batch.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut query =
+            QueryBuilder::<MySql>::new("select * from restaurant where kakao_place_id in (");
+        let mut separated = query.separated(", ");
+        for (r, ..) in batch.iter() {
+            separated.push_bind(&r.kakao_place_id);
+        }
+        separated.push_unseparated(") order by created_at desc, id desc");
+        let result = query
+            .build_query_as::<Restaurant>()
+            .fetch_all(&mut **tx)
+            .await;
         match result {
+            Ok(v) => Ok(v
+                .into_iter()
+                .map(|r| (r.kakao_place_id.clone(), r))
+                .collect()),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+
+    async fn upsert_restaurants(
+        restaurants: &[Restaurant],
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<(), error::Error> {
+        if restaurants.is_empty() {
+            return Ok(());
+        }
+        let mut query =
+            QueryBuilder::<MySql>::new(format!("insert into restaurant ({RESTAURANT_COLUMNS}) "));
+        query.push_values(restaurants, |mut b, r| {
+            b.push_bind(&r.id)
+                .push_bind(&r.name)
+                .push_bind(&r.address)
+                .push_bind(&r.road_address)
+                .push_bind(&r.phone)
+                .push_bind(&r.place_url)
+                .push_bind(r.x)
+                .push_bind(r.y)
+                .push_bind(&r.kakao_place_id)
+                .push_bind(&r.category_group_code)
+                .push_bind(&r.category_name)
+                .push_bind(&r.primary_category)
+                .push_bind(r.api_called_at)
+                .push_bind(r.scraped_at)
+                .push_bind(r.created_at)
+                .push_bind(r.updated_at)
+                .push_bind(r.missing_since)
+                .push_bind(r.miss_count)
+                .push_bind(r.deleted_at);
+        });
+        query.push(
+            " on duplicate key update name = values(name), address = values(address), road_address = values(road_address), phone = values(phone), place_url = values(place_url), x = values(x), y = values(y), category_group_code = values(category_group_code), category_name = values(category_name), primary_category = values(primary_category), api_called_at = values(api_called_at), updated_at = values(updated_at), missing_since = null, miss_count = 0, deleted_at = null",
+        );
+        match query.build().execute(&mut **tx).await {
+            Ok(..) => Ok(()),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+
+    async fn upsert_place_batch(
+        places: &[Place],
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<(), error::Error> {
+        let mut query = QueryBuilder::<MySql>::new(
+            "insert into place (id, name, address, road_address, phone, place_url, x, y, kakao_place_id, category_group_code, category_name, api_called_at, created_at, updated_at, missing_since, miss_count, deleted_at) ",
+        );
+        query.push_values(places, |mut b, p| {
+            b.push_bind(&p.id)
+                .push_bind(&p.name)
+                .push_bind(&p.address)
+                .push_bind(&p.road_address)
+                .push_bind(&p.phone)
+                .push_bind(&p.place_url)
+                .push_bind(p.x)
+                .push_bind(p.y)
+                .push_bind(&p.kakao_place_id)
+                .push_bind(&p.category_group_code)
+                .push_bind(&p.category_name)
+                .push_bind(p.api_called_at)
+                .push_bind(p.created_at)
+                .push_bind(p.updated_at)
+                .push_bind(p.missing_since)
+                .push_bind(p.miss_count)
+                .push_bind(p.deleted_at);
+        });
+        query.push(
+            " on duplicate key update name = values(name), address = values(address), road_address = values(road_address), phone = values(phone), place_url = values(place_url), x = values(x), y = values(y), category_group_code = values(category_group_code), category_name = values(category_name), api_called_at = values(api_called_at), updated_at = values(updated_at), missing_since = null, miss_count = 0, deleted_at = null",
+        );
+        match query.build().execute(&mut **tx).await {
             Ok(..) => Ok(()),
             Err(e) => Err(error::Error::SqlExecutionFailed(e)),
         }
     }
+
+    async fn find_categories(
+        rids: &[&str],
+        conn: &mut MySqlConnection,
+    ) -> Result<Vec<Category>, error::Error> {
+        if rids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut query = QueryBuilder::<MySql>::new(
+            "select * from restaurant_categories where restaurant_id in (",
+        );
+        let mut separated = query.separated(", ");
+        for rid in rids.iter() {
+            separated.push_bind(*rid);
+        }
+        separated.push_unseparated(")");
+        match query.build_query_as::<Category>().fetch_all(conn).await {
+            Ok(v) => Ok(v),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+
+    /// Inserts categories, overwriting source and confidence of stored ones.
+    async fn insert_categories(
+        categories: &[&Category],
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<(), error::Error> {
+        if categories.is_empty() {
+            return Ok(());
+        }
+        let mut query = QueryBuilder::<MySql>::new(
+            "insert into restaurant_categories (restaurant_id, categories, source, confidence) ",
+        );
+        query.push_values(categories, |mut b, c| {
+            b.push_bind(&c.restaurant_id)
+                .push_bind(&c.categories)
+                .push_bind(&c.source)
+                .push_bind(c.confidence);
+        });
+        query.push(
+            " on duplicate key update source = values(source), confidence = values(confidence)",
+        );
+        match query.build().execute(&mut **tx).await {
+            Ok(..) => Ok(()),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+
+    async fn delete_categories(
+        categories: &[&Category],
+        tx: &mut Transaction<'_, MySql>,
+    ) -> Result<usize, error::Error> {
+        if categories.is_empty() {
+            return Ok(0);
+        }
+        let mut query = QueryBuilder::<MySql>::new(
+            "delete from restaurant_categories where (restaurant_id, categories) in (",
+        );
+        let mut separated = query.separated(", ");
+        for c in categories.iter() {
+            separated.push("(");
+            separated.push_bind_unseparated(&c.restaurant_id);
+            separated.push_unseparated(", ");
+            separated.push_bind_unseparated(&c.categories);
+            separated.push_unseparated(")");
+        }
+        separated.push_unseparated(")");
+        match query.build().execute(&mut **tx).await {
+            Ok(r) => Ok(r.rows_affected() as usize),
+            Err(e) => Err(error::Error::SqlExecutionFailed(e)),
+        }
+    }
+}
+
+/// Restaurants synced before their group was recorded can be either food
+/// group, so they only belong to a crawl of both.
+pub fn includes_unknown_group(groups: &[api::Category]) -> bool {
+    [api::Category::Restaurant, api::Category::Cafe]
+        .iter()
+        .all(|c| groups.contains(c))
+}
+
+/// Whether a stored row with `category_group_code` belongs to a crawl of `groups`.
+pub fn in_groups(category_group_code: Option<&str>, groups: &[api::Category]) -> bool {
+    match category_group_code {
+        Some(code) => groups.iter().any(|c| c.code() == code),
+        None => includes_unknown_group(groups),
+    }
+}
+
+fn push_group_filter(
+    query: &mut QueryBuilder<'_, MySql>,
+    groups: &[&api::Category],
+    include_unknown: bool,
+) {
+    query.push(" and (category_group_code in (");
+    let mut separated = query.separated(", ");
+    for c in groups.iter() {
+        separated.push_bind(c.code());
+    }
+    separated.push_unseparated(")");
+    if include_unknown {
+        query.push(" or category_group_code is null");
+    }
+    query.push(")");
 }
 
 #[tokio::main]
@@ -75,26 +685,29 @@ pub async fn test_connection() -> Result<(), sqlx::Error> {
     use models::*;
     dotenv::dotenv().ok();
 
-    let url = Const::DbUrl.value();
+    let config = crate::config::Config::load(None).unwrap();
+    let url = config.db_url().unwrap();
 
-    let pool = MySqlPool::connect(&url).await?;
+    let pool = MySqlPool::connect(url).await?;
 
     sqlx::query("delete from restaurant where id=?")
         .bind("some_id")
         .execute(&pool)
         .await?;
 
-    sqlx::query("insert into restaurant values (?, ?, ?, ?, ?, ?, ?, ?)")
-        .bind("some_id")
-        .bind("some_name")
-        .bind("some_address")
-        .bind(36)
-        .bind(127)
-        .bind("some_kakao_id")
-        .bind(chrono::Utc::now())
-        .bind(chrono::Utc::now())
-        .execute(&pool)
-        .await?;
+    sqlx::query(
+        "insert into restaurant (id, name, address, x, y, kakao_place_id, api_called_at, created_at) values (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind("some_id")
+    .bind("some_name")
+    .bind("some_address")
+    .bind(127.0)
+    .bind(36.0)
+    .bind("some_kakao_id")
+    .bind(chrono::Utc::now())
+    .bind(chrono::Utc::now())
+    .execute(&pool)
+    .await?;
 
     let restaurant: Restaurant = sqlx::query_as("select * from restaurant where id=?")
         .bind("some_id")
@@ -125,7 +738,7 @@ pub async fn test_connection() -> Result<(), sqlx::Error> {
 
 //     let url = Const::DB_URL.value();
 
-//     let pool = MySqlPool::connect(&url).await?;
+//     let pool = MySqlPool::connect(url).await?;
 //     sqlx::query("delete from restaurant where 1=1")
 //         .execute(&pool)
 //         .await?;
@@ -141,9 +754,10 @@ pub async fn select_all() -> Result<(), sqlx::Error> {
     use dotenv;
     dotenv::dotenv().ok();
 
-    let url = Const::DbUrl.value();
+    let config = crate::config::Config::load(None).unwrap();
+    let url = config.db_url().unwrap();
 
-    let pool = MySqlPool::connect(&url).await?;
+    let pool = MySqlPool::connect(url).await?;
 
     let restaurants: Vec<Restaurant> = sqlx::query_as("select * from restaurant")
         .fetch_all(&pool)
@@ -162,3 +776,88 @@ pub async fn select_all() -> Result<(), sqlx::Error> {
     );
     Ok(())
 }
+
+#[test]
+fn test_migrations_cover_models() {
+    let sql: String = MIGRATOR
+        .iter()
+        .map(|m| m.sql.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let now = chrono::Utc::now();
+    let restaurant = serde_json::to_value(Restaurant {
+        id: String::new(),
+        name: String::new(),
+        address: String::new(),
+        road_address: None,
+        phone: None,
+        place_url: None,
+        x: 0.0,
+        y: 0.0,
+        kakao_place_id: String::new(),
+        category_group_code: None,
+        category_name: None,
+        primary_category: None,
+        api_called_at: now,
+        scraped_at: None,
+        created_at: now,
+        updated_at: None,
+        missing_since: None,
+        miss_count: 0,
+        deleted_at: None,
+    })
+    .unwrap();
+    let category = serde_json::to_value(Category {
+        restaurant_id: String::new(),
+        categories: String::new(),
+        source: models::SOURCE_NAME.to_string(),
+        confidence: 0.5,
+    })
+    .unwrap();
+    let place = serde_json::to_value(Place {
+        id: String::new(),
+        name: String::new(),
+        address: String::new(),
+        road_address: None,
+        phone: None,
+        place_url: None,
+        x: 0.0,
+        y: 0.0,
+        kakao_place_id: String::new(),
+        category_group_code: String::new(),
+        category_name: String::new(),
+        api_called_at: now,
+        created_at: now,
+        updated_at: None,
+        missing_since: None,
+        miss_count: 0,
+        deleted_at: None,
+    })
+    .unwrap();
+    for column in [restaurant, category, place]
+        .iter()
+        .flat_map(|v| v.as_object().unwrap().keys())
+    {
+        assert!(
+            sql.contains(&format!("{column} ")),
+            "no migration creates column {column}"
+        );
+    }
+    let versions: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    assert!(versions.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn test_in_groups() {
+    use api::Category::*;
+    assert!(in_groups(Some("FD6"), &[Restaurant]));
+    assert!(!in_groups(Some("CE7"), &[Restaurant]));
+    assert!(!in_groups(None, &[Restaurant]));
+    assert!(in_groups(None, &[Cafe, Restaurant]));
+    assert!(!in_groups(Some("FD6"), &[]));
+}
+
+#[test]
+fn test_batch_size_within_bind_limit() {
+    assert_eq!(RESTAURANT_COLUMNS.split(',').count(), RESTAURANT_BINDS);
+}
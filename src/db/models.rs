@@ -6,17 +6,62 @@ pub struct Restaurant {
     pub id: String,
     pub name: String,
     pub address: String,
+    pub road_address: Option<String>,
+    pub phone: Option<String>,
+    pub place_url: Option<String>,
     pub x: f64,
     pub y: f64,
     pub kakao_place_id: String,
+    /// FD6 or CE7, `None` for rows synced before the group was recorded.
+    pub category_group_code: Option<String>,
+    /// Kakao category path as returned, e.g. "음식점 > 일식 > 초밥,롤".
+    pub category_name: Option<String>,
+    /// The most specific `CategoryType` of the path, `None` when nothing mapped.
+    pub primary_category: Option<String>,
     pub api_called_at: chrono::DateTime<chrono::Utc>,
     pub scraped_at: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// First time a complete crawl of the place's area did not return it.
+    pub missing_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Complete crawls in a row that did not return the place.
+    pub miss_count: i32,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
 pub struct Category {
     pub restaurant_id: String,
     pub categories: String,
+    /// `SOURCE_KAKAO` when mapped from the Kakao category path,
+    /// `SOURCE_NAME` when inferred from the place name.
+    pub source: String,
+    /// 1 for categories from Kakao, below 1 for inferred ones.
+    pub confidence: f64,
+}
+
+pub const SOURCE_KAKAO: &str = "kakao";
+pub const SOURCE_NAME: &str = "name";
+
+/// A place of a non-food category group, such as a pharmacy or a subway station.
+/// Its Kakao category path is kept as is instead of being mapped to `CategoryType`.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct Place {
+    pub id: String,
+    pub name: String,
+    pub address: String,
+    pub road_address: Option<String>,
+    pub phone: Option<String>,
+    pub place_url: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub kakao_place_id: String,
+    pub category_group_code: String,
+    pub category_name: String,
+    pub api_called_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub missing_since: Option<chrono::DateTime<chrono::Utc>>,
+    pub miss_count: i32,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
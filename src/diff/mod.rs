@@ -0,0 +1,227 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::db::models::{Category, Restaurant};
+
+/// Coordinates closer than this (about 1cm) are treated as unchanged.
+const COORD_EPSILON: f64 = 1e-7;
+
+/// What a sync of crawled places would do to the stored rows of the same area.
+#[derive(Serialize, Debug, Default)]
+pub struct Diff {
+    pub new: Vec<NewPlace>,
+    pub changed: Vec<ChangedPlace>,
+    /// Stored places inside the area that the crawl did not return.
+    pub missing: Vec<MissingPlace>,
+    pub unchanged: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewPlace {
+    pub kakao_place_id: String,
+    pub name: String,
+    pub address: String,
+    pub x: f64,
+    pub y: f64,
+    pub categories: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChangedPlace {
+    pub id: String,
+    pub kakao_place_id: String,
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MissingPlace {
+    pub id: String,
+    pub kakao_place_id: String,
+    pub name: String,
+    pub address: String,
+}
+
+pub fn diff(
+    crawled: &[(Restaurant, Vec<Category>)],
+    stored: &[(Restaurant, Vec<Category>)],
+) -> Diff {
+    let by_kakao_id: HashMap<&str, &(Restaurant, Vec<Category>)> = stored
+        .iter()
+        .map(|e| (e.0.kakao_place_id.as_str(), e))
+        .collect();
+    let mut result = Diff::default();
+
+    for (r, c) in crawled.iter() {
+        match by_kakao_id.get(r.kakao_place_id.as_str()) {
+            Some((before, before_c)) => {
+                let changes = changes(before, before_c, r, c);
+                match changes.is_empty() {
+                    true => result.unchanged += 1,
+                    false => result.changed.push(ChangedPlace {
+                        id: before.id.clone(),
+                        kakao_place_id: r.kakao_place_id.clone(),
+                        name: r.name.clone(),
+                        changes,
+                    }),
+                }
+            }
+            None => result.new.push(NewPlace {
+                kakao_place_id: r.kakao_place_id.clone(),
+                name: r.name.clone(),
+                address: r.address.clone(),
+                x: r.x,
+                y: r.y,
+                categories: category_names(c).into_iter().collect(),
+            }),
+        }
+    }
+
+    let seen: HashSet<&str> = crawled
+        .iter()
+        .map(|(r, ..)| r.kakao_place_id.as_str())
+        .collect();
+    result.missing = stored
+        .iter()
+        .filter(|(r, ..)| !seen.contains(r.kakao_place_id.as_str()))
+        .map(|(r, ..)| MissingPlace {
+            id: r.id.clone(),
+            kakao_place_id: r.kakao_place_id.clone(),
+            name: r.name.clone(),
+            address: r.address.clone(),
+        })
+        .collect();
+    result
+}
+
+fn changes(
+    before: &Restaurant,
+    before_c: &[Category],
+    after: &Restaurant,
+    after_c: &[Category],
+) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &'static str, before: String, after: String| {
+        if before != after {
+            changes.push(FieldChange {
+                field,
+                before,
+                after,
+            });
+        }
+    };
+    let optional = |v: &Option<String>| v.clone().unwrap_or_default();
+    compare("name", before.name.clone(), after.name.clone());
+    compare("address", before.address.clone(), after.address.clone());
+    compare(
+        "road_address",
+        optional(&before.road_address),
+        optional(&after.road_address),
+    );
+    compare("phone", optional(&before.phone), optional(&after.phone));
+    compare(
+        "place_url",
+        optional(&before.place_url),
+        optional(&after.place_url),
+    );
+    if (before.x - after.x).abs() > COORD_EPSILON || (before.y - after.y).abs() > COORD_EPSILON {
+        compare(
+            "coordinates",
+            format!("{}, {}", before.x, before.y),
+            format!("{}, {}", after.x, after.y),
+        );
+    }
+    let (before_c, after_c) = (category_names(before_c), category_names(after_c));
+    compare(
+        "categories",
+        before_c.into_iter().collect::<Vec<_>>().join(", "),
+        after_c.into_iter().collect::<Vec<_>>().join(", "),
+    );
+    changes
+}
+
+fn category_names(c: &[Category]) -> BTreeSet<String> {
+    c.iter().map(|c| c.categories.clone()).collect()
+}
+
+#[cfg(test)]
+fn entry(kakao_place_id: &str, name: &str, categories: &[&str]) -> (Restaurant, Vec<Category>) {
+    let now = chrono::Utc::now();
+    let id = format!("id-{kakao_place_id}");
+    (
+        Restaurant {
+            id: id.clone(),
+            name: name.to_string(),
+            address: "서울 마포구 서교동 1".to_string(),
+            road_address: None,
+            phone: Some("02-000-0000".to_string()),
+            place_url: None,
+            x: 126.92,
+            y: 37.55,
+            kakao_place_id: kakao_place_id.to_string(),
+            category_group_code: Some("FD6".to_string()),
+            category_name: None,
+            primary_category: None,
+            api_called_at: now,
+            scraped_at: None,
+            created_at: now,
+            updated_at: Some(now),
+            missing_since: None,
+            miss_count: 0,
+            deleted_at: None,
+        },
+        categories
+            .iter()
+            .map(|c| Category {
+                restaurant_id: id.clone(),
+                categories: c.to_string(),
+                source: crate::db::models::SOURCE_KAKAO.to_string(),
+                confidence: 1.0,
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn test_diff() {
+    let stored = vec![
+        entry("1", "스시야", &["JAPANESE", "SUSHI"]),
+        entry("2", "피자스쿨", &["WESTERN_FOOD", "PIZZA"]),
+        entry("3", "닫은 가게", &["KOREAN"]),
+    ];
+    let mut renamed = entry("2", "피자스쿨 홍대점", &["PIZZA", "WESTERN_FOOD"]);
+    renamed.0.x += 0.001;
+    let crawled = vec![
+        entry("1", "스시야", &["SUSHI", "JAPANESE"]),
+        renamed,
+        entry("4", "새 가게", &["CAFE_DESSERT"]),
+    ];
+
+    let diff = diff(&crawled, &stored);
+    assert_eq!(diff.unchanged, 1);
+    assert_eq!(diff.new.len(), 1);
+    assert_eq!(diff.new[0].kakao_place_id, "4");
+    assert_eq!(diff.missing.len(), 1);
+    assert_eq!(diff.missing[0].kakao_place_id, "3");
+    assert_eq!(diff.changed.len(), 1);
+    let changed = &diff.changed[0];
+    assert_eq!(changed.id, "id-2");
+    let fields: Vec<_> = changed.changes.iter().map(|c| c.field).collect();
+    assert_eq!(fields, vec!["name", "coordinates"]);
+    assert_eq!(
+        changed.changes[0],
+        FieldChange {
+            field: "name",
+            before: "피자스쿨".to_string(),
+            after: "피자스쿨 홍대점".to_string(),
+        }
+    );
+}
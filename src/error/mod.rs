@@ -2,14 +2,38 @@
 pub enum Error {
     InvalidLatitudeRange,
     InvalidLongitudeRange,
+    EmptyRect,
+    InvalidRadius,
+    InvalidGeoJson(String),
+    InvalidArgument(String),
+    MissingConfig(String),
+    InvalidConfig(String),
+    ApiRequestFailed(reqwest::Error),
+    ApiUnauthorized(String),
+    ApiQuotaExceeded(String),
+    ApiStatus(u16, String),
+    ApiDecodeFailed(serde_json::Error),
+    TooManyFailures { failed: usize, requests: usize },
+    TaskFailed(tokio::task::JoinError),
+    Interrupted,
     DbConnectionFailed(sqlx::Error),
     SqlExecutionFailed(sqlx::Error),
+    MigrationFailed(sqlx::migrate::MigrateError),
+    FileIoFailed(std::io::Error),
+    SerializationFailed(serde_json::Error),
 }
 
 impl std::error::Error for Error {}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Self::InvalidArgument(msg) => write!(f, "{msg}"),
+            Self::MissingConfig(key) => write!(f, "missing config: {key}"),
+            Self::InvalidConfig(msg) => write!(f, "invalid config: {msg}"),
+            Self::InvalidGeoJson(msg) => write!(f, "invalid geojson: {msg}"),
+            Self::Interrupted => write!(f, "interrupted, progress saved to the checkpoint"),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
@@ -0,0 +1,363 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use itertools::Itertools;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    db::models::{Category, Place, Restaurant},
+    error,
+    types::{CategoryType, Language},
+};
+
+/// The file formats places can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    Ndjson,
+    /// One row per place, restaurant and place columns side by side.
+    Csv,
+    /// A FeatureCollection of points.
+    GeoJson,
+}
+
+impl Format {
+    /// Guesses the format from the file extension, ndjson when there is none.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Format::Csv,
+            Some("geojson") => Format::GeoJson,
+            _ => Format::Ndjson,
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "geojson" => Ok(Format::GeoJson),
+            _ => Err(error::Error::InvalidArgument(format!(
+                "unknown export format {s}, expected ndjson, csv or geojson"
+            ))),
+        }
+    }
+}
+
+/// Columns of restaurants with their category names. Places have a subset of
+/// them, so one header fits both.
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "address",
+    "road_address",
+    "phone",
+    "place_url",
+    "x",
+    "y",
+    "kakao_place_id",
+    "category_group_code",
+    "category_name",
+    "primary_category",
+    "categories",
+    "api_called_at",
+    "scraped_at",
+    "created_at",
+    "updated_at",
+    "missing_since",
+    "miss_count",
+    "deleted_at",
+];
+
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    restaurant: &'a Restaurant,
+    categories: Vec<&'a str>,
+}
+
+/// Opens `path` for writing, `-` meaning stdout.
+pub fn open(path: &Path) -> Result<Box<dyn Write>, error::Error> {
+    if path.as_os_str() == "-" {
+        return Ok(Box::new(BufWriter::new(io::stdout())));
+    }
+    match File::create(path) {
+        Ok(f) => Ok(Box::new(BufWriter::new(f))),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+pub fn write<W: Write>(
+    format: Format,
+    entries: &[(Restaurant, Vec<Category>)],
+    places: &[Place],
+    w: W,
+) -> Result<(), error::Error> {
+    match format {
+        Format::Ndjson => write_ndjson(entries, places, w),
+        Format::Csv => write_csv(entries, places, w),
+        Format::GeoJson => write_geojson(entries, places, w),
+    }
+}
+
+/// Writes one JSON object per restaurant, with its category names inlined,
+/// followed by one per place of the other groups.
+pub fn write_ndjson<W: Write>(
+    entries: &[(Restaurant, Vec<Category>)],
+    places: &[Place],
+    mut w: W,
+) -> Result<(), error::Error> {
+    for (r, c) in entries.iter() {
+        let record = Record {
+            restaurant: r,
+            categories: c.iter().map(|c| c.categories.as_str()).collect(),
+        };
+        write_line(&mut w, &record)?;
+    }
+    for p in places.iter() {
+        write_line(&mut w, p)?;
+    }
+    match w.flush() {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+/// Writes a header row, even when there is nothing to export, then a row per
+/// place. Categories are joined with `|`, missing values are empty.
+pub fn write_csv<W: Write>(
+    entries: &[(Restaurant, Vec<Category>)],
+    places: &[Place],
+    w: W,
+) -> Result<(), error::Error> {
+    let rows = rows(entries, places)?;
+    let mut w = csv::Writer::from_writer(w);
+    if let Err(e) = w.write_record(CSV_COLUMNS) {
+        return Err(csv_error(e));
+    }
+    for row in rows.iter() {
+        let record = CSV_COLUMNS.iter().map(|c| match row.get(*c) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(a)) => a.iter().filter_map(Value::as_str).join("|"),
+            Some(v) => v.to_string(),
+        });
+        if let Err(e) = w.write_record(record) {
+            return Err(csv_error(e));
+        }
+    }
+    match w.flush() {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+/// Writes a FeatureCollection with a point per place, its fields as properties.
+pub fn write_geojson<W: Write>(
+    entries: &[(Restaurant, Vec<Category>)],
+    places: &[Place],
+    mut w: W,
+) -> Result<(), error::Error> {
+    let features: Vec<Value> = rows(entries, places)?
+        .into_iter()
+        .map(|properties| {
+            let coordinates = [&properties["x"], &properties["y"]];
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": coordinates },
+                "properties": properties,
+            })
+        })
+        .collect();
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    write_line(&mut w, &collection)?;
+    match w.flush() {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+/// Restaurants with their category names inlined, then places, as JSON objects.
+fn rows(
+    entries: &[(Restaurant, Vec<Category>)],
+    places: &[Place],
+) -> Result<Vec<Map<String, Value>>, error::Error> {
+    let restaurants = entries.iter().map(|(r, c)| {
+        object(Record {
+            restaurant: r,
+            categories: c.iter().map(|c| c.categories.as_str()).collect(),
+        })
+    });
+    restaurants.chain(places.iter().map(object)).collect()
+}
+
+fn object<T: Serialize>(value: T) -> Result<Map<String, Value>, error::Error> {
+    match serde_json::to_value(value).and_then(serde_json::from_value) {
+        Ok(o) => Ok(o),
+        Err(e) => Err(error::Error::SerializationFailed(e)),
+    }
+}
+
+#[derive(Serialize)]
+struct CatalogEntry {
+    name: &'static CategoryType,
+    parent: Option<CategoryType>,
+    depth: usize,
+    ko: &'static str,
+    en: &'static str,
+}
+
+/// Writes every category that can be stored, with its parent, depth and
+/// display names, as `{"categories": [...]}` in declaration order.
+pub fn write_catalog<W: Write>(mut w: W) -> Result<(), error::Error> {
+    let categories = CategoryType::ALL
+        .iter()
+        .filter(|c| **c != CategoryType::UNDEFINED)
+        .map(|c| CatalogEntry {
+            name: c,
+            parent: c.parent(),
+            depth: c.depth(),
+            ko: c.label(Language::Ko),
+            en: c.label(Language::En),
+        })
+        .collect::<Vec<_>>();
+    let catalog = serde_json::json!({ "categories": categories });
+    if let Err(e) = serde_json::to_writer_pretty(&mut w, &catalog) {
+        return Err(error::Error::SerializationFailed(e));
+    }
+    match w.write_all(b"\n").and_then(|_| w.flush()) {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+fn csv_error(e: csv::Error) -> error::Error {
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => error::Error::FileIoFailed(e),
+        kind => error::Error::FileIoFailed(io::Error::other(format!("{kind:?}"))),
+    }
+}
+
+fn write_line<W: Write, T: Serialize>(w: &mut W, value: &T) -> Result<(), error::Error> {
+    if let Err(e) = serde_json::to_writer(&mut *w, value) {
+        return Err(error::Error::SerializationFailed(e));
+    }
+    match w.write_all(b"\n") {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+#[cfg(test)]
+fn sample() -> (Vec<(Restaurant, Vec<Category>)>, Vec<Place>) {
+    let restaurant: Restaurant = serde_json::from_value(serde_json::json!({
+        "id": "r1", "name": "스시, 야", "address": "서울 마포구 서교동 395-166",
+        "road_address": null, "phone": "02-332-8286", "place_url": null,
+        "x": 126.921, "y": 37.556, "kakao_place_id": "26338954",
+        "category_group_code": "FD6", "category_name": "음식점 > 일식 > 초밥,롤",
+        "primary_category": "SUSHI", "api_called_at": "2024-01-01T00:00:00Z",
+        "scraped_at": null, "created_at": "2024-01-01T00:00:00Z", "updated_at": null,
+        "missing_since": null, "miss_count": 0, "deleted_at": null
+    }))
+    .unwrap();
+    let place: Place = serde_json::from_value(serde_json::json!({
+        "id": "p1", "name": "온누리약국", "address": "서울 마포구 동교동 1",
+        "road_address": null, "phone": null, "place_url": null,
+        "x": 126.922, "y": 37.557, "kakao_place_id": "1234",
+        "category_group_code": "PM9", "category_name": "의료,건강 > 약국",
+        "api_called_at": "2024-01-01T00:00:00Z", "created_at": "2024-01-01T00:00:00Z",
+        "updated_at": null, "missing_since": null, "miss_count": 0, "deleted_at": null
+    }))
+    .unwrap();
+    let categories = ["JAPANESE", "SUSHI"].map(|c| Category {
+        restaurant_id: "r1".to_string(),
+        categories: c.to_string(),
+        source: crate::db::models::SOURCE_KAKAO.to_string(),
+        confidence: 1.0,
+    });
+    (vec![(restaurant, categories.to_vec())], vec![place])
+}
+
+#[test]
+fn test_write_csv() {
+    let (entries, places) = sample();
+    let mut out = Vec::new();
+    write(Format::Csv, &entries, &places, &mut out).unwrap();
+    let mut reader = csv::Reader::from_reader(out.as_slice());
+    let headers = reader.headers().unwrap().clone();
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 2);
+    let column = |row: &csv::StringRecord, name: &str| {
+        let i = headers.iter().position(|h| h == name).unwrap();
+        row[i].to_string()
+    };
+    assert_eq!(column(&rows[0], "name"), "스시, 야");
+    assert_eq!(column(&rows[0], "categories"), "JAPANESE|SUSHI");
+    assert_eq!(column(&rows[0], "road_address"), "");
+    assert_eq!(column(&rows[1], "categories"), "");
+    assert_eq!(column(&rows[1], "category_name"), "의료,건강 > 약국");
+    assert_eq!(column(&rows[1], "x"), "126.922");
+    // every field of the models has a column
+    for row in self::rows(&entries, &places).unwrap() {
+        assert!(row.keys().all(|k| CSV_COLUMNS.contains(&k.as_str())));
+    }
+
+    let mut out = Vec::new();
+    write(Format::Csv, &[], &[], &mut out).unwrap();
+    let mut reader = csv::Reader::from_reader(out.as_slice());
+    assert_eq!(reader.headers().unwrap().len(), CSV_COLUMNS.len());
+    assert_eq!(reader.records().count(), 0);
+}
+
+#[test]
+fn test_write_geojson() {
+    let (entries, places) = sample();
+    let mut out = Vec::new();
+    write(Format::GeoJson, &entries, &places, &mut out).unwrap();
+    let collection: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(collection["type"], "FeatureCollection");
+    let features = collection["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(
+        features[0]["geometry"],
+        serde_json::json!({ "type": "Point", "coordinates": [126.921, 37.556] })
+    );
+    assert_eq!(features[0]["properties"]["categories"][1], "SUSHI");
+    assert_eq!(features[1]["properties"]["category_group_code"], "PM9");
+
+    assert_eq!(
+        Format::from_path(Path::new("mapo.geojson")),
+        Format::GeoJson
+    );
+    assert_eq!(Format::from_path(Path::new("-")), Format::Ndjson);
+    assert!("parquet".parse::<Format>().is_err());
+}
+
+#[test]
+fn test_write_catalog() {
+    let mut out = Vec::new();
+    write_catalog(&mut out).unwrap();
+    let catalog: Value = serde_json::from_slice(&out).unwrap();
+    let categories = catalog["categories"].as_array().unwrap();
+    assert_eq!(categories.len(), CategoryType::ALL.len() - 1);
+    assert!(categories.iter().all(|c| c["name"] != "UNDEFINED"));
+
+    let pizza = categories.iter().find(|c| c["name"] == "PIZZA").unwrap();
+    assert_eq!(pizza["parent"], "WESTERN_FOOD");
+    assert_eq!(pizza["depth"], 1);
+    assert_eq!(pizza["ko"], "피자");
+    assert_eq!(pizza["en"], "Pizza");
+    let korean = categories.iter().find(|c| c["name"] == "KOREAN").unwrap();
+    assert_eq!(korean["parent"], Value::Null);
+    assert_eq!(korean["depth"], 0);
+}
@@ -1,116 +1,745 @@
 mod api;
+mod cli;
+mod config;
 mod db;
+mod diff;
 mod error;
+mod export;
 mod types;
 mod utils;
+use cli::{Command, DryRunArgs, ExportArgs, ReplayArgs};
+use config::Config;
 use itertools::Itertools;
+use std::{collections::HashSet, io::Write, str::FromStr};
 use types::*;
 
-struct ArgInput {
-    sw: Coords,
-    ne: Coords,
-}
-
-impl ArgInput {
-    fn new() -> Result<ArgInput, error::Error> {
-        let mut input = std::env::args()
-            .skip(1)
-            .take(5)
-            .flat_map(|s| s.parse::<f64>());
-        let (x1, y1, x2, y2) = (
-            input.next().unwrap(),
-            input.next().unwrap(),
-            input.next().unwrap(),
-            input.next().unwrap(),
+pub async fn run() -> Result<(), error::Error> {
+    let cli = cli::Cli::from_env()?;
+    let mut config = Config::load(cli.config.as_deref())?;
+    match cli.command {
+        Command::Sync(args) => {
+            config.apply_sync_args(&args);
+            config.validate_db()?;
+            let area = config.area(&args.crawl)?;
+            sync(&config, &area).await?;
+            remove_checkpoint(&config)
+        }
+        Command::DryRun(args) => {
+            config.apply_args(&args.crawl);
+            config.validate_db()?;
+            dry_run(&config, &args).await?;
+            remove_checkpoint(&config)
+        }
+        Command::Export(args) => {
+            config.apply_args(&args.crawl);
+            export(&config, &args).await
+        }
+        Command::Replay(args) => {
+            config.apply_category_args(&args.categories);
+            replay(&config, &args)
+        }
+        Command::Catalog(args) => export::write_catalog(export::open(&args.output)?),
+        Command::Stats => {
+            config.validate_db()?;
+            stats(&config).await
+        }
+        Command::Migrate => {
+            config.validate_db()?;
+            migrate(&config).await
+        }
+    }
+}
+
+/// The checkpoint is kept until what was crawled has been used, so a failed
+/// database write can be retried with `--resume` without crawling again.
+fn remove_checkpoint(config: &Config) -> Result<(), error::Error> {
+    match config.crawl.checkpoint.as_ref() {
+        Some(path) => api::Checkpoint::remove(path),
+        None => Ok(()),
+    }
+}
+
+async fn connect(config: &Config) -> Result<db::DbPool, error::Error> {
+    Ok(db::DbPool::new(config.db_url()?)
+        .await?
+        .with_batch_size(config.db.batch_size)
+        .with_transaction_size(config.db.transaction_size))
+}
+
+async fn sync(config: &Config, area: &Area) -> Result<(), error::Error> {
+    let crawled = crawl(config, area).await?;
+    let reconciled = reconciled_groups(config, &crawled)?;
+
+    let db = connect(config).await?;
+    println!("upserting into database...");
+    let summary = db.upsert_all(crawled.restaurants).await?;
+    println!(
+        "upserting success. restaurant inserted: {}, updated: {}, category inserted: {}, updated: {}, deleted: {}",
+        summary.inserted,
+        summary.updated,
+        summary.categories_inserted,
+        summary.categories_updated,
+        summary.categories_deleted
+    );
+    if !crawled.places.is_empty() {
+        let written = db.upsert_places(&crawled.places).await?;
+        println!("place upserted: {written}");
+    }
+
+    if !crawled.complete {
+        println!("crawl incomplete, skipping missing place detection");
+        return Ok(());
+    }
+    if reconciled.is_empty() {
+        println!("no category group crawled, skipping missing place detection");
+        return Ok(());
+    }
+    let seen = crawled.seen.iter().map(String::as_str).collect();
+    let summary = db
+        .mark_missing(area, &reconciled, &seen, config.db.max_misses as i32)
+        .await?;
+    println!(
+        "missing places marked: {}, soft deleted: {}",
+        summary.marked, summary.deleted
+    );
+    Ok(())
+}
+
+/// The category groups whose stored places a crawl may mark missing. A place
+/// can only be told apart from a failed or truncated request when the whole
+/// area was crawled, so none otherwise.
+fn reconciled_groups(
+    config: &Config,
+    crawled: &Crawled,
+) -> Result<Vec<api::Category>, error::Error> {
+    match crawled.complete {
+        true => config.groups(),
+        false => Ok(vec![]),
+    }
+}
+
+async fn dry_run(config: &Config, args: &DryRunArgs) -> Result<(), error::Error> {
+    let area = config.area(&args.crawl)?;
+    let crawled = crawl(config, &area).await?;
+    let db = connect(config).await?;
+    let groups = config.groups()?;
+    // Keyword results can be of any food group, so stored rows of their
+    // groups are looked up too, to tell new places from known ones.
+    let mut lookup = groups.clone();
+    for (r, ..) in crawled.restaurants.iter() {
+        let group = r
+            .category_group_code
+            .as_deref()
+            .map(api::Category::from_str);
+        if let Some(Ok(c)) = group {
+            if !lookup.contains(&c) {
+                lookup.push(c);
+            }
+        }
+    }
+    let stored = db.find_in_area(&area, &lookup).await?;
+    let mut diff = diff::diff(&crawled.restaurants, &stored);
+    // Only places of the crawled groups can be missing, and only when the
+    // whole area was crawled, the same as sync.
+    if groups.is_empty() {
+        println!("no category group crawled, missing places are not reported");
+        diff.missing.clear();
+    } else if crawled.complete {
+        let crawled_groups: HashSet<&str> = stored
+            .iter()
+            .filter(|(r, ..)| db::in_groups(r.category_group_code.as_deref(), &groups))
+            .map(|(r, ..)| r.id.as_str())
+            .collect();
+        diff.missing
+            .retain(|p| crawled_groups.contains(p.id.as_str()));
+    } else {
+        println!("crawl incomplete, missing places are not reported");
+        diff.missing.clear();
+    }
+
+    println!(
+        "dry run, nothing written. stored restaurants in range: {}",
+        stored.len()
+    );
+    if !crawled.places.is_empty() {
+        println!(
+            "{} places of non-food groups are not diffed",
+            crawled.places.len()
+        );
+    }
+    for p in diff.new.iter() {
+        println!(
+            "+ {} ({}) {}",
+            p.name,
+            p.kakao_place_id,
+            p.categories.join(", ")
         );
-        let (sw, ne) = Self::make_coords_pair(x1, y1, x2, y2)?;
-        Ok(ArgInput { sw, ne })
     }
+    for p in diff.changed.iter() {
+        println!("~ {} ({})", p.name, p.kakao_place_id);
+        for c in p.changes.iter() {
+            println!("    {}: {} -> {}", c.field, c.before, c.after);
+        }
+    }
+    for p in diff.missing.iter() {
+        println!("- {} ({}) {}", p.name, p.kakao_place_id, p.address);
+    }
+    println!(
+        "new: {}, changed: {}, missing: {}, unchanged: {}",
+        diff.new.len(),
+        diff.changed.len(),
+        diff.missing.len(),
+        diff.unchanged
+    );
 
-    fn make_coords_pair(
-        x1: f64,
-        y1: f64,
-        x2: f64,
-        y2: f64,
-    ) -> Result<(Coords, Coords), error::Error> {
-        let pair = (Coords::new(x1, y1)?, Coords::new(x2, y2)?);
-        if !pair.0.at_south_west_from(&pair.1) {
-            return Ok((pair.1, pair.0));
+    if let Some(output) = args.output.as_ref() {
+        let mut w = export::open(output)?;
+        if let Err(e) = serde_json::to_writer_pretty(&mut w, &diff) {
+            return Err(error::Error::SerializationFailed(e));
+        }
+        if let Err(e) = w.flush() {
+            return Err(error::Error::FileIoFailed(e));
         }
-        Ok(pair)
     }
+    Ok(())
 }
 
-pub async fn run() -> Result<(), error::Error> {
-    let config = ArgInput::new()?;
+async fn export(config: &Config, args: &ExportArgs) -> Result<(), error::Error> {
+    let area = config.area(&args.crawl)?;
+    let (restaurants, places) = match args.from_db {
+        true => {
+            config.validate_db()?;
+            let db = connect(config).await?;
+            (
+                db.find_in_area(&area, &config.groups()?).await?,
+                db.find_places_in_area(&area).await?,
+            )
+        }
+        false => {
+            let crawled = crawl(config, &area).await?;
+            (crawled.restaurants, crawled.places)
+        }
+    };
+    let format = match args.format {
+        Some(f) => f,
+        None => export::Format::from_path(&args.output),
+    };
     println!(
-        "search for data from kakao in range ({}, {}), ({}, {})",
-        config.sw.x, config.sw.y, config.ne.x, config.ne.y
+        "writing {} places to {} as {format:?}",
+        restaurants.len() + places.len(),
+        args.output.display()
     );
-    let set = api::get_from_kakao(config.sw, config.ne).await;
-    println!("kakao done. data from kakao: {}", set.len());
-
-    let entry: Vec<(db::models::Restaurant, Vec<db::models::Category>)> = set
-        .into_iter()
-        .map(|d| {
-            let rid = uuid::Uuid::new_v4().to_string();
-            let now = chrono::Utc::now();
-            let r = db::models::Restaurant {
-                id: rid.clone(),
-                name: d.place_name,
-                address: d.address_name,
-                x: d.x.parse().unwrap(),
-                y: d.y.parse().unwrap(),
-                kakao_place_id: d.id,
-                api_called_at: now,
-                scraped_at: None,
-                created_at: now,
-                updated_at: Some(now),
-            };
-            let categories = get_categories_from(d.category_name, &rid);
-            (r, categories)
-        })
-        .collect();
+    export::write(format, &restaurants, &places, export::open(&args.output)?)?;
+    match args.from_db {
+        true => Ok(()),
+        false => remove_checkpoint(config),
+    }
+}
 
-    let db = db::DbPool::new().await?;
-    println!("inserting into database...");
-    db.insert_all(entry).await?;
-    println!("inserting success");
+async fn migrate(config: &Config) -> Result<(), error::Error> {
+    let db = connect(config).await?;
+    let applied = db.migrate().await?;
+    for m in applied.iter() {
+        println!("applied {m}");
+    }
+    println!("database schema is up to date");
     Ok(())
 }
 
-fn get_categories_from(c: String, rid: &str) -> Vec<db::models::Category> {
+async fn stats(config: &Config) -> Result<(), error::Error> {
+    let db = connect(config).await?;
+    let stats = db.stats().await?;
+    println!("restaurant: {}", stats.restaurants);
+    for (category, n) in stats.categories.iter() {
+        println!("{category}: {n}");
+    }
+    for (group, n) in stats.places.iter() {
+        println!("place {group}: {n}");
+    }
+    Ok(())
+}
+
+/// What a crawl returned, split by the table each place is stored in.
+struct Crawled {
+    restaurants: Vec<(db::models::Restaurant, Vec<db::models::Category>)>,
+    places: Vec<db::models::Place>,
+    /// Whether every request succeeded without any cell overflowing, i.e.
+    /// whether the result holds every place of the area.
+    complete: bool,
+    /// Labels of restaurant category paths the mapping did not know.
+    unmapped: UnmappedReport,
+    /// Places left out because their coordinates did not parse.
+    skipped: usize,
+    /// Kakao ids of every place returned, skipped ones included, so a place
+    /// is never marked missing for a bad response.
+    seen: HashSet<String>,
+}
+
+async fn crawl(config: &Config, area: &Area) -> Result<Crawled, error::Error> {
+    config.validate_crawl()?;
+    let map = config.category_map()?;
+    println!("search for data from kakao in {area}");
+    let crawled = api::get_from_kakao(config, area).await?;
+    println!("kakao done. data from kakao: {}", crawled.documents.len());
+    if !crawled.overflowed.is_empty() {
+        println!(
+            "{} cells still had more results than kakao pages through:",
+            crawled.overflowed.len()
+        );
+        for o in crawled.overflowed.iter() {
+            println!(
+                "{} ({}, {}), ({}, {}): {} places",
+                o.query, o.rect.sw.x, o.rect.sw.y, o.rect.ne.x, o.rect.ne.y, o.total_count
+            );
+        }
+    }
+    if !crawled.failed.is_empty() {
+        println!(
+            "partial result, {} of {} requests failed:",
+            crawled.failed.len(),
+            crawled.requests
+        );
+        for f in crawled.failed.iter() {
+            println!(
+                "{} ({}, {}), ({}, {}) page {}: {}",
+                f.query, f.rect.sw.x, f.rect.sw.y, f.rect.ne.x, f.rect.ne.y, f.page, f.error
+            );
+        }
+    }
+
+    let complete = crawled.failed.is_empty() && crawled.overflowed.is_empty();
+    let crawled = Crawled {
+        complete,
+        ..split(crawled.documents, &map)
+    };
+    report_skipped(crawled.skipped);
+    report_unmapped(config, &crawled.unmapped)?;
+    Ok(crawled)
+}
+
+/// Rebuilds places from archived responses, with the category mapping of
+/// this build rather than the one they were crawled with.
+fn replay(config: &Config, args: &ReplayArgs) -> Result<(), error::Error> {
+    let map = config.category_map()?;
+    let records = api::archive::read(&args.archive)?;
+    if let (Some(first), Some(last)) = (records.first(), records.last()) {
+        println!(
+            "replaying {} responses requested from {} to {}",
+            records.len(),
+            first.requested_at,
+            last.requested_at
+        );
+    }
+    let replayed = split(api::archive::replay(records), &map);
+    report_skipped(replayed.skipped);
+    report_unmapped(config, &replayed.unmapped)?;
+    println!(
+        "writing {} places to {}",
+        replayed.restaurants.len() + replayed.places.len(),
+        args.output.display()
+    );
+    export::write_ndjson(
+        &replayed.restaurants,
+        &replayed.places,
+        export::open(&args.output)?,
+    )
+}
+
+fn split(documents: HashSet<api::dto::Document>, map: &CategoryMap) -> Crawled {
+    let seen = documents.iter().map(|d| d.id.clone()).collect();
+    let (food, other): (Vec<_>, Vec<_>) = documents.into_iter().partition(is_food);
+    let mut unmapped = UnmappedReport::default();
+    for d in food.iter() {
+        for label in map.unmapped(&d.category_name) {
+            unmapped.record(&label, &d.place_name);
+        }
+    }
+    let total = food.len() + other.len();
+    let restaurants: Vec<_> = food.into_iter().filter_map(|d| to_entry(d, map)).collect();
+    let places: Vec<_> = other.into_iter().filter_map(to_place).collect();
+    Crawled {
+        skipped: total - restaurants.len() - places.len(),
+        restaurants,
+        places,
+        complete: false,
+        unmapped,
+        seen,
+    }
+}
+
+fn report_skipped(skipped: usize) {
+    if skipped > 0 {
+        println!("{skipped} places skipped, their coordinates are not numbers");
+    }
+}
+
+/// Prints the most frequent unmapped labels, and writes all of them to
+/// `categories.unmapped_report` when set.
+fn report_unmapped(config: &Config, report: &UnmappedReport) -> Result<(), error::Error> {
+    if !report.is_empty() {
+        println!("{} category labels are not mapped:", report.len());
+        for (label, l) in report.labels().into_iter().take(10) {
+            println!(
+                "{label}: {} places, e.g. {}",
+                l.count,
+                l.examples.join(", ")
+            );
+        }
+    }
+    let Some(path) = config.categories.unmapped_report.as_ref() else {
+        return Ok(());
+    };
+    let mut w = export::open(path)?;
+    if let Err(e) = serde_json::to_writer_pretty(&mut w, report) {
+        return Err(error::Error::SerializationFailed(e));
+    }
+    match w.flush() {
+        Ok(..) => Ok(()),
+        Err(e) => Err(error::Error::FileIoFailed(e)),
+    }
+}
+
+/// Keyword results can come without a category group, those are told apart
+/// by their category path instead.
+fn is_food(d: &api::dto::Document) -> bool {
+    match d.category_group_code.parse::<api::Category>() {
+        Ok(c) => c.is_food(),
+        Err(..) => d.category_name.starts_with("음식점"),
+    }
+}
+
+/// Kakao sends coordinates as strings. A document whose coordinates do not
+/// parse, from a bad response or a corrupt archive line, has none.
+fn coords(d: &api::dto::Document) -> Option<(f64, f64)> {
+    match (d.x.trim().parse::<f64>(), d.y.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) if x.is_finite() && y.is_finite() => Some((x, y)),
+        _ => None,
+    }
+}
+
+fn to_entry(
+    d: api::dto::Document,
+    map: &CategoryMap,
+) -> Option<(db::models::Restaurant, Vec<db::models::Category>)> {
+    let (x, y) = coords(&d)?;
+    let rid = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now();
+    let mut categories = get_categories_from(d.category_name.clone(), &rid, map);
+    let mut primary = primary_category_from(&d.category_name, map);
+    if let Some((c, inferred)) = infer_categories(&d.place_name, &categories, &rid, map) {
+        categories.extend(inferred);
+        primary = Some(c);
+    }
+    let r = db::models::Restaurant {
+        id: rid.clone(),
+        name: d.place_name,
+        address: d.address_name,
+        road_address: non_empty(d.road_address_name),
+        phone: non_empty(d.phone),
+        place_url: non_empty(d.place_url),
+        x,
+        y,
+        kakao_place_id: d.id,
+        category_group_code: non_empty(d.category_group_code),
+        category_name: non_empty(d.category_name.clone()),
+        primary_category: primary.map(|c| c.name().to_string()),
+        api_called_at: now,
+        scraped_at: None,
+        created_at: now,
+        updated_at: Some(now),
+        missing_since: None,
+        miss_count: 0,
+        deleted_at: None,
+    };
+    Some((r, categories))
+}
+
+fn to_place(d: api::dto::Document) -> Option<db::models::Place> {
+    let (x, y) = coords(&d)?;
+    let now = chrono::Utc::now();
+    Some(db::models::Place {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: d.place_name,
+        address: d.address_name,
+        road_address: non_empty(d.road_address_name),
+        phone: non_empty(d.phone),
+        place_url: non_empty(d.place_url),
+        x,
+        y,
+        kakao_place_id: d.id,
+        category_group_code: d.category_group_code,
+        category_name: d.category_name,
+        api_called_at: now,
+        created_at: now,
+        updated_at: Some(now),
+        missing_since: None,
+        miss_count: 0,
+        deleted_at: None,
+    })
+}
+
+/// Kakao sends missing optional fields as empty strings.
+fn non_empty(s: String) -> Option<String> {
+    match s.trim().is_empty() {
+        true => None,
+        false => Some(s),
+    }
+}
+
+/// Maps every segment of a Kakao category path. The parents of what was
+/// mapped are added as well, so filtering on a category finds its children.
+fn get_categories_from(c: String, rid: &str, map: &CategoryMap) -> Vec<db::models::Category> {
     c.split('>')
-        .map(|s| get_category_from(s, rid))
-        .unique_by(|c| c.categories.clone())
-        .filter(|c| c.categories.ne(CategoryType::UNDEFINED.name()))
+        .map(|s| map.map(s))
+        .filter(|c| *c != CategoryType::UNDEFINED)
+        .flat_map(|c| {
+            let mut chain = c.ancestors();
+            chain.reverse();
+            chain.push(c);
+            chain
+        })
+        .unique()
+        .map(|c| get_category_from(&c, rid, db::models::SOURCE_KAKAO, 1.0))
         .collect()
 }
 
-fn get_category_from(c: &str, rid: &str) -> db::models::Category {
+fn get_category_from(
+    c: &CategoryType,
+    rid: &str,
+    source: &str,
+    confidence: f64,
+) -> db::models::Category {
     db::models::Category {
         restaurant_id: rid.to_string(),
-        categories: CategoryType::from(c.trim()).name().to_string(),
+        categories: c.name().to_string(),
+        source: source.to_string(),
+        confidence,
     }
 }
 
+/// Infers a category from the place name when the Kakao path gave nothing
+/// more specific than `OTHERS`. Returns it with the rows to add for it and
+/// its parents, marked as inferred.
+fn infer_categories(
+    name: &str,
+    categories: &[db::models::Category],
+    rid: &str,
+    map: &CategoryMap,
+) -> Option<(CategoryType, Vec<db::models::Category>)> {
+    if categories
+        .iter()
+        .any(|c| c.categories != CategoryType::OTHERS.name())
+    {
+        return None;
+    }
+    let (category, confidence) = map.infer(name)?;
+    let mut chain = category.ancestors();
+    chain.reverse();
+    chain.push(category.clone());
+    let inferred = chain
+        .iter()
+        .map(|c| get_category_from(c, rid, db::models::SOURCE_NAME, confidence))
+        .collect();
+    Some((category, inferred))
+}
+
+/// The deepest category of the path, the later one when several are as deep.
+fn primary_category_from(c: &str, map: &CategoryMap) -> Option<CategoryType> {
+    c.split('>')
+        .map(|s| map.map(s))
+        .filter(|c| *c != CategoryType::UNDEFINED)
+        .max_by_key(CategoryType::depth)
+}
+
 #[test]
 fn test_get_categories_from() {
     let result: Vec<String> = get_categories_from(
         "음식점 > 카페 > 커피전문점 > 커피사피엔스".to_string(),
         "some",
+        &CategoryMap::default(),
     )
     .into_iter()
     .map(|c| c.categories)
     .collect();
     assert_eq!(result, vec!["CAFE_DESSERT".to_string()]);
 
-    let result: Vec<_> = get_categories_from("음식점 > 양식 > 피자 > 피자스쿨".to_string(), "some")
-        .into_iter()
-        .map(|c| c.categories)
-        .collect();
+    let result: Vec<_> = get_categories_from(
+        "음식점 > 양식 > 피자 > 피자스쿨".to_string(),
+        "some",
+        &CategoryMap::default(),
+    )
+    .into_iter()
+    .map(|c| c.categories)
+    .collect();
     assert_eq!(
         result,
         vec!["WESTERN_FOOD".to_string(), "PIZZA".to_string()]
     );
+
+    // the parent is added when the path skips it
+    let result: Vec<_> =
+        get_categories_from("음식점 > 회".to_string(), "some", &CategoryMap::default())
+            .into_iter()
+            .map(|c| c.categories)
+            .collect();
+    assert_eq!(result, vec!["KOREAN", "SEA_FOOD", "RAW_FISH"]);
+}
+
+#[test]
+fn test_primary_category_from() {
+    let map = CategoryMap::default();
+    let primary = primary_category_from("음식점 > 한식 > 해물,생선 > 회", &map);
+    assert_eq!(primary, Some(CategoryType::RAW_FISH));
+    let primary = primary_category_from("음식점 > 카페 > 커피전문점", &map);
+    assert_eq!(primary, Some(CategoryType::CAFE_DESSERT));
+    assert_eq!(primary_category_from("음식점 > 뷔페", &map), None);
+}
+
+/// A restaurant document with the given name and category path.
+#[cfg(test)]
+fn document(id: usize, name: &str, category_name: &str) -> api::dto::Document {
+    api::dto::Document {
+        place_name: name.to_string(),
+        category_name: category_name.to_string(),
+        ..api::mock::document(api::Category::Restaurant, id, 126.921, 37.556)
+    }
+}
+
+#[test]
+fn test_to_entry() {
+    let d = api::dto::Document {
+        phone: "02-332-8286".to_string(),
+        road_address_name: "".to_string(),
+        ..document(26338954, "스시야", "음식점 > 일식 > 초밥,롤")
+    };
+    let (r, categories) = to_entry(d, &CategoryMap::default()).unwrap();
+    assert_eq!(r.kakao_place_id, "26338954");
+    assert_eq!(r.phone.as_deref(), Some("02-332-8286"));
+    assert_eq!(
+        r.place_url.as_deref(),
+        Some("http://place.map.kakao.com/26338954")
+    );
+    assert_eq!(r.road_address, None);
+    assert_eq!(r.category_group_code.as_deref(), Some("FD6"));
+    assert_eq!(r.category_name.as_deref(), Some("음식점 > 일식 > 초밥,롤"));
+    assert_eq!(r.primary_category.as_deref(), Some("SUSHI"));
+    assert!(categories.iter().all(|c| c.restaurant_id == r.id));
+}
+
+#[test]
+fn test_to_entry_infers_from_name() {
+    let map = CategoryMap::default();
+
+    let (r, categories) = to_entry(document(1, "홍대 마라탕", "음식점 > 퓨전요리"), &map).unwrap();
+    assert_eq!(r.primary_category.as_deref(), Some("CHINESE"));
+    let rows: Vec<_> = categories
+        .iter()
+        .map(|c| (c.categories.as_str(), c.source.as_str()))
+        .collect();
+    assert_eq!(rows, vec![("OTHERS", "kakao"), ("CHINESE", "name")]);
+    assert!(categories[1].confidence < 1.0);
+
+    let (r, categories) = to_entry(document(1, "OO 돈까스", "음식점"), &map).unwrap();
+    assert_eq!(r.primary_category.as_deref(), Some("PORK_CUTLET_UDON"));
+    let rows: Vec<_> = categories.iter().map(|c| c.categories.as_str()).collect();
+    assert_eq!(rows, vec!["JAPANESE", "PORK_CUTLET_UDON"]);
+    assert!(categories.iter().all(|c| c.source == "name"));
+
+    // Kakao categories are not second-guessed
+    let (r, categories) =
+        to_entry(document(1, "마라탕 피자", "음식점 > 양식 > 피자"), &map).unwrap();
+    assert_eq!(r.primary_category.as_deref(), Some("PIZZA"));
+    assert!(categories.iter().all(|c| c.source == "kakao"));
+}
+
+#[test]
+fn test_split_reports_unmapped() {
+    let documents = [
+        document(1, "뷔페A", "음식점 > 뷔페"),
+        document(2, "뷔페B", "음식점 > 뷔페 > 해산물뷔페"),
+        document(3, "스시야", "음식점 > 일식 > 초밥,롤"),
+    ];
+    let crawled = split(documents.into_iter().collect(), &CategoryMap::default());
+    // places with unmapped labels are kept, only without categories
+    assert_eq!(crawled.restaurants.len(), 3);
+    let labels = crawled.unmapped.labels();
+    assert_eq!(labels.len(), 2);
+    assert_eq!(labels[0].0, "뷔페");
+    assert_eq!(labels[0].1.count, 2);
+}
+
+#[test]
+fn test_split_skips_bad_coordinates() {
+    let mut corrupt = api::mock::document(api::Category::Restaurant, 1, 126.92, 37.55);
+    corrupt.y = "".to_string();
+    let mut pharmacy = api::mock::document(api::Category::Pharmacy, 2, 126.92, 37.55);
+    pharmacy.x = "NaN".to_string();
+    let documents = [
+        corrupt,
+        pharmacy,
+        api::mock::document(api::Category::Restaurant, 3, 126.92, 37.55),
+    ];
+    let crawled = split(documents.into_iter().collect(), &CategoryMap::default());
+    assert_eq!(crawled.restaurants.len(), 1);
+    assert_eq!(crawled.restaurants[0].0.kakao_place_id, "3");
+    assert!(crawled.places.is_empty());
+    assert_eq!(crawled.skipped, 2);
+    // skipped places were still returned and must not get a miss
+    assert!(["1", "2", "3"].iter().all(|id| crawled.seen.contains(*id)));
+}
+
+#[tokio::test]
+async fn test_incomplete_crawl_marks_nothing_missing() {
+    use api::mock;
+    let rect = Rect::from_corners(126.91, 37.57, 126.93, 37.58).unwrap();
+    let area = Area::Rect(rect.clone());
+    let places = || {
+        let mut places = mock::grid(api::Category::Restaurant, &rect, 4, 4, 0);
+        places.extend(mock::grid(api::Category::Cafe, &rect, 3, 3, 100));
+        places
+    };
+    let config_for = |server: &mock::MockKakao| {
+        let mut config = Config::default();
+        config.kakao.api_key = Some(mock::API_KEY.to_string());
+        config.kakao.api_url = server.url();
+        config.kakao.keyword_url = server.keyword_url();
+        config.crawl.requests_per_second = 1000.0;
+        config.crawl.max_failure_ratio = 0.9;
+        config
+    };
+
+    let server = mock::MockKakao::start(mock::MockConfig {
+        places: places(),
+        ..Default::default()
+    })
+    .await;
+    let config = config_for(&server);
+    let crawled = crawl(&config, &area).await.unwrap();
+    assert!(crawled.complete);
+    assert_eq!(
+        reconciled_groups(&config, &crawled).unwrap(),
+        vec![api::Category::Restaurant, api::Category::Cafe]
+    );
+
+    let failing = mock::MockKakao::start(mock::MockConfig {
+        places: places(),
+        fail_every: Some((3, 400)),
+        ..Default::default()
+    })
+    .await;
+    let crawled = crawl(&config_for(&failing), &area).await.unwrap();
+    assert!(!crawled.complete);
+    assert!(reconciled_groups(&config, &crawled).unwrap().is_empty());
+
+    let hotspot = Coords::new(126.9201, 37.5751).unwrap();
+    let mut crowded = places();
+    crowded.extend(mock::cluster(api::Category::Restaurant, &hotspot, 60, 1000));
+    let overflowing = mock::MockKakao::start(mock::MockConfig {
+        places: crowded,
+        ..Default::default()
+    })
+    .await;
+    let crawled = crawl(&config_for(&overflowing), &area).await.unwrap();
+    assert!(!crawled.complete);
+    assert!(reconciled_groups(&config, &crawled).unwrap().is_empty());
 }
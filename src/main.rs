@@ -1,8 +1,10 @@
 use dotenv::dotenv;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     dotenv().ok();
-    api_data_sync::run().await?;
-    Ok(())
+    if let Err(e) = api_data_sync::run().await {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,525 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::{CategoryType, CATEGORY_MAP, NAME_KEYWORDS};
+use crate::error;
+
+/// Places listed per unmapped label in the report.
+const EXAMPLES: usize = 3;
+
+/// Confidence of a category inferred from a word of the name that is the
+/// keyword, ends with it ("명동돈까스"), or only contains it.
+const WORD_CONFIDENCE: f64 = 0.8;
+const SUFFIX_CONFIDENCE: f64 = 0.6;
+const CONTAINS_CONFIDENCE: f64 = 0.4;
+
+/// Kakao category labels to `CategoryType`, the built-in `CATEGORY_MAP`
+/// extended or overridden by a mapping file. Labels are normalized before
+/// any lookup, see `normalize`.
+pub struct CategoryMap {
+    /// Alias to the label it stands for.
+    aliases: HashMap<String, String>,
+    labels: HashMap<String, Mapped>,
+    /// Prefix and regex rules, the one to try first first.
+    rules: Vec<Rule>,
+    /// Name keywords, longest first.
+    keywords: Vec<(String, CategoryType)>,
+}
+
+struct Mapped {
+    category: CategoryType,
+    priority: i32,
+}
+
+struct Rule {
+    matcher: Matcher,
+    category: CategoryType,
+    priority: i32,
+}
+
+enum Matcher {
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl Rule {
+    fn matches(&self, label: &str) -> bool {
+        match &self.matcher {
+            Matcher::Prefix(p) => label.starts_with(p.as_str()),
+            Matcher::Regex(r) => r.is_match(label),
+        }
+    }
+
+    /// Higher priority first, then prefixes before regexes and longer
+    /// prefixes before shorter ones. Equal rules keep the file order.
+    fn order(&self) -> (std::cmp::Reverse<i32>, usize, std::cmp::Reverse<usize>) {
+        let (kind, len) = match &self.matcher {
+            Matcher::Prefix(p) => (0, p.len()),
+            Matcher::Regex(..) => (1, 0),
+        };
+        (
+            std::cmp::Reverse(self.priority),
+            kind,
+            std::cmp::Reverse(len),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingFile {
+    /// Label to category name, at priority 0.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    /// Alias to the label it is mapped as.
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    rules: Vec<RuleConfig>,
+    /// Place name keyword to category name, on top of the built-in ones.
+    #[serde(default)]
+    keywords: BTreeMap<String, String>,
+}
+
+/// One of `label`, `prefix` or `regex`, matched against the normalized label.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    label: Option<String>,
+    prefix: Option<String>,
+    regex: Option<String>,
+    category: String,
+    #[serde(default)]
+    priority: i32,
+}
+
+impl Default for CategoryMap {
+    fn default() -> Self {
+        CategoryMap {
+            aliases: HashMap::new(),
+            labels: CATEGORY_MAP
+                .iter()
+                .map(|(label, t)| {
+                    let mapped = Mapped {
+                        category: t.clone(),
+                        priority: 0,
+                    };
+                    (normalize(label), mapped)
+                })
+                .collect(),
+            rules: Vec::new(),
+            keywords: NAME_KEYWORDS
+                .iter()
+                .map(|(k, t)| (normalize(k), t.clone()))
+                .collect(),
+        }
+        .with_sorted_keywords()
+    }
+}
+
+impl CategoryMap {
+    /// Reads a TOML file with a `labels` table of Kakao label to category
+    /// name, an `aliases` table of label to label, and `rules` with a prefix
+    /// or regex. A label mapped to `UNDEFINED` is known but left out, and is
+    /// not reported as unmapped.
+    ///
+    /// Of everything matching a label the highest `priority` wins. Exact
+    /// labels win over rules of the same priority.
+    pub fn from_file(path: &Path) -> Result<CategoryMap, error::Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        match Self::from_toml(&text) {
+            Ok(map) => Ok(map),
+            Err(msg) => Err(error::Error::InvalidConfig(format!(
+                "{}: {msg}",
+                path.display()
+            ))),
+        }
+    }
+
+    fn from_toml(text: &str) -> Result<CategoryMap, String> {
+        let file: MappingFile = match toml::from_str(text) {
+            Ok(f) => f,
+            Err(e) => return Err(e.message().to_string()),
+        };
+        let category = |name: &str| match CategoryType::from_name(name) {
+            Some(t) => Ok(t),
+            None => Err(format!("unknown category {name}")),
+        };
+        let mut map = CategoryMap::default();
+        for (label, name) in file.labels {
+            let mapped = Mapped {
+                category: category(&name)?,
+                priority: 0,
+            };
+            map.labels.insert(normalize(&label), mapped);
+        }
+        for (alias, label) in file.aliases {
+            map.aliases.insert(normalize(&alias), normalize(&label));
+        }
+        for r in file.rules {
+            let category = category(&r.category)?;
+            let matcher = match (r.label, r.prefix, r.regex) {
+                (Some(label), None, None) => {
+                    let mapped = Mapped {
+                        category,
+                        priority: r.priority,
+                    };
+                    map.labels.insert(normalize(&label), mapped);
+                    continue;
+                }
+                (None, Some(prefix), None) => Matcher::Prefix(normalize(&prefix)),
+                (None, None, Some(regex)) => match Regex::new(&regex) {
+                    Ok(r) => Matcher::Regex(r),
+                    Err(e) => return Err(format!("invalid regex {regex}: {e}")),
+                },
+                _ => {
+                    return Err(format!(
+                        "rule for {} needs exactly one of label, prefix and regex",
+                        r.category
+                    ))
+                }
+            };
+            map.rules.push(Rule {
+                matcher,
+                category,
+                priority: r.priority,
+            });
+        }
+        map.rules.sort_by_key(Rule::order);
+        for (keyword, name) in file.keywords {
+            let keyword = normalize(&keyword);
+            let category = category(&name)?;
+            // Inference only runs for places Kakao left at OTHERS, and
+            // UNDEFINED is never stored, so neither is worth guessing.
+            if matches!(category, CategoryType::OTHERS | CategoryType::UNDEFINED) {
+                return Err(format!(
+                    "keyword {keyword} maps to {}, which is not inferred",
+                    category.name()
+                ));
+            }
+            match map.keywords.iter_mut().find(|(k, ..)| *k == keyword) {
+                Some(entry) => entry.1 = category,
+                None => map.keywords.push((keyword, category)),
+            }
+        }
+        Ok(map.with_sorted_keywords())
+    }
+
+    fn with_sorted_keywords(mut self) -> CategoryMap {
+        self.keywords
+            .sort_by_key(|(k, ..)| std::cmp::Reverse(k.chars().count()));
+        self
+    }
+
+    /// Guesses a category from the words of a place name, with a confidence
+    /// below 1. The keyword matching most closely wins, the longer one of
+    /// equally close ones.
+    pub fn infer(&self, name: &str) -> Option<(CategoryType, f64)> {
+        let words: Vec<String> = name.split_whitespace().map(normalize).collect();
+        let mut best: Option<(&CategoryType, f64)> = None;
+        for (keyword, category) in self.keywords.iter() {
+            let confidence = words
+                .iter()
+                .map(|w| match w {
+                    w if w == keyword => WORD_CONFIDENCE,
+                    w if w.ends_with(keyword.as_str()) => SUFFIX_CONFIDENCE,
+                    w if w.contains(keyword.as_str()) => CONTAINS_CONFIDENCE,
+                    _ => 0.0,
+                })
+                .fold(0.0, f64::max);
+            if confidence > best.map_or(0.0, |(.., c)| c) {
+                best = Some((category, confidence));
+            }
+        }
+        best.map(|(c, confidence)| (c.clone(), confidence))
+    }
+
+    /// `None` for labels nothing matches.
+    pub fn get(&self, label: &str) -> Option<&CategoryType> {
+        let label = normalize(label);
+        let label = self.aliases.get(&label).unwrap_or(&label);
+        let exact = self.labels.get(label);
+        let rule = self.rules.iter().find(|r| r.matches(label));
+        match (exact, rule) {
+            (Some(e), Some(r)) if r.priority > e.priority => Some(&r.category),
+            (Some(e), ..) => Some(&e.category),
+            (None, Some(r)) => Some(&r.category),
+            (None, None) => None,
+        }
+    }
+
+    /// Maps a single label, unknown labels becoming `UNDEFINED`.
+    pub fn map(&self, label: &str) -> CategoryType {
+        match self.get(label) {
+            Some(t) => t.clone(),
+            None => CategoryType::UNDEFINED,
+        }
+    }
+
+    /// Normalized labels of a Kakao category path that nothing matches. The
+    /// first segment is the group itself ("음식점") and never mapped. Kakao
+    /// ends paths of franchises with the brand ("음식점 > 치킨 > 교촌치킨"),
+    /// so a last segment is only reported when no label above it is known.
+    pub fn unmapped(&self, path: &str) -> Vec<String> {
+        let labels: Vec<String> = path
+            .split('>')
+            .skip(1)
+            .map(normalize)
+            .filter(|label| !label.is_empty())
+            .collect();
+        let known = |label: &str| self.get(label).is_some();
+        labels
+            .iter()
+            .enumerate()
+            .filter(|(i, label)| {
+                let brand = *i + 1 == labels.len() && labels[..*i].iter().any(|l| known(l));
+                !known(label) && !brand
+            })
+            .map(|(_, label)| label.clone())
+            .collect()
+    }
+}
+
+/// Makes variants of a Kakao label compare equal: full-width forms become
+/// ASCII, whitespace is dropped, the separators Kakao uses between
+/// alternatives become a single comma, and ASCII letters are lowercased.
+/// "찌개, 전골", "찌개，전골" and "찌개 · 전골" all become "찌개,전골".
+pub fn normalize(label: &str) -> String {
+    let mut normalized = String::with_capacity(label.len());
+    for c in label.chars() {
+        let c = match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            '·' | '・' | '/' | '、' => ',',
+            c => c,
+        };
+        if c.is_whitespace() || (c == ',' && normalized.ends_with(',')) {
+            continue;
+        }
+        normalized.push(c.to_ascii_lowercase());
+    }
+    normalized.trim_matches(',').to_string()
+}
+
+/// Kakao category labels the map did not know, with how often they came up.
+#[derive(Serialize, Default, Debug)]
+pub struct UnmappedReport {
+    labels: BTreeMap<String, UnmappedLabel>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct UnmappedLabel {
+    pub count: usize,
+    /// Names of the first places the label was seen on.
+    pub examples: Vec<String>,
+}
+
+impl UnmappedReport {
+    pub fn record(&mut self, label: &str, place_name: &str) {
+        let entry = self.labels.entry(label.to_string()).or_default();
+        entry.count += 1;
+        if entry.examples.len() < EXAMPLES {
+            entry.examples.push(place_name.to_string());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Most frequent first.
+    pub fn labels(&self) -> Vec<(&str, &UnmappedLabel)> {
+        let mut labels: Vec<_> = self.labels.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        labels.sort_by_key(|(.., l)| std::cmp::Reverse(l.count));
+        labels
+    }
+}
+
+#[test]
+fn test_category_map_from_file() {
+    let path = std::env::temp_dir().join(format!("{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        r#"
+        [labels]
+        "뷔페" = "OTHERS"
+        "피자" = "ITALY"
+        "커피전문점" = "UNDEFINED"
+        "#,
+    )
+    .unwrap();
+    let map = CategoryMap::from_file(&path).unwrap();
+    assert_eq!(map.map("뷔페"), CategoryType::OTHERS);
+    assert_eq!(map.map(" 피자 "), CategoryType::ITALY);
+    assert_eq!(map.map("한식"), CategoryType::KOREAN);
+    // brands below a known label are not categories
+    assert!(map
+        .unmapped("음식점 > 카페 > 커피전문점 > 커피 사피엔스")
+        .is_empty());
+    assert!(map.unmapped("음식점 > 치킨 > 교촌치킨").is_empty());
+    assert_eq!(
+        map.unmapped("음식점 > 샤브샤브 > 월남쌈 > 등촌샤브칼국수"),
+        vec!["월남쌈"]
+    );
+    assert_eq!(
+        map.unmapped("음식점 > 한정식 > 궁중요리"),
+        vec!["한정식", "궁중요리"]
+    );
+
+    std::fs::write(&path, "[labels]\n\"뷔페\" = \"BUFFET\"\n").unwrap();
+    assert!(CategoryMap::from_file(&path).is_err());
+    for name in ["UNDEFINED", "OTHERS"] {
+        std::fs::write(&path, format!("[keywords]\n\"뷔페\" = \"{name}\"\n")).unwrap();
+        assert!(CategoryMap::from_file(&path).is_err());
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(normalize("찌개, 전골"), "찌개,전골");
+    assert_eq!(normalize("찌개，전골"), "찌개,전골");
+    assert_eq!(normalize(" 찌개 · 전골 "), "찌개,전골");
+    assert_eq!(normalize("호프,,요리주점"), "호프,요리주점");
+    assert_eq!(normalize("ＢＢＱ치킨"), "bbq치킨");
+}
+
+#[test]
+fn test_kakao_label_variants() {
+    // labels as Kakao returns them, next to the spelling of the built-in table
+    let map = CategoryMap::default();
+    let variants = [
+        ("찌개,전골", CategoryType::KOREAN_STEW),
+        ("해물, 생선", CategoryType::SEA_FOOD),
+        ("육류，고기", CategoryType::MEAT),
+        ("초밥, 롤", CategoryType::SUSHI),
+        ("돈까스 ,우동", CategoryType::PORK_CUTLET_UDON),
+        ("호프, 요리주점", CategoryType::HOF_PUB),
+        ("제과 , 베이커리", CategoryType::BAKERY),
+        ("떡·한과", CategoryType::RICE_CAKE),
+        ("스테이크,립", CategoryType::STEAK_RIB),
+        ("멕시칸, 브라질", CategoryType::MEXICAN),
+        ("일본식 라면", CategoryType::RAMEN),
+        ("실내 포장마차", CategoryType::INDOOR_STALLS),
+    ];
+    for (label, category) in variants {
+        assert_eq!(map.map(label), category, "{label}");
+    }
+}
+
+#[test]
+fn test_category_rules() {
+    let map = CategoryMap::from_toml(
+        r#"
+        [labels]
+        "커피전문점" = "CAFE_DESSERT"
+
+        [aliases]
+        "스시" = "초밥,롤"
+
+        [[rules]]
+        prefix = "일본식"
+        category = "JAPANESE"
+
+        [[rules]]
+        prefix = "일본식주"
+        category = "IZAKAYA"
+
+        [[rules]]
+        regex = "(브런치|디저트)카페$"
+        category = "CAFE_DESSERT"
+
+        [[rules]]
+        regex = "카페$"
+        category = "OTHERS"
+        priority = -1
+
+        [[rules]]
+        regex = "전문점$"
+        category = "OTHERS"
+        priority = 5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(map.map("스시"), CategoryType::SUSHI);
+    // longer prefixes first, exact labels before rules of the same priority
+    assert_eq!(map.map("일본식 주점"), CategoryType::IZAKAYA);
+    assert_eq!(map.map("일본식라면"), CategoryType::RAMEN);
+    assert_eq!(map.map("일본식카레"), CategoryType::JAPANESE);
+    assert_eq!(map.map("디저트카페"), CategoryType::CAFE_DESSERT);
+    assert_eq!(map.map("북카페"), CategoryType::OTHERS);
+    // a rule of higher priority wins over an exact label
+    assert_eq!(map.map("커피전문점"), CategoryType::OTHERS);
+
+    let invalid = [
+        "[[rules]]\nprefix = \"일식\"\nregex = \"일식\"\ncategory = \"JAPANESE\"",
+        "[[rules]]\nregex = \"(\"\ncategory = \"JAPANESE\"",
+        "[aliases]\n\"스시\" = 1",
+    ];
+    for text in invalid {
+        assert!(CategoryMap::from_toml(text).is_err(), "{text}");
+    }
+}
+
+#[test]
+fn test_infer_from_name() {
+    let map = CategoryMap::from_toml(
+        r#"
+        [keywords]
+        "훠궈" = "CHINESE"
+        "김밥" = "LUNCH_BOX"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        map.infer("홍대 마라탕"),
+        Some((CategoryType::CHINESE, WORD_CONFIDENCE))
+    );
+    assert_eq!(
+        map.infer("OO 돈까스"),
+        Some((CategoryType::PORK_CUTLET_UDON, WORD_CONFIDENCE))
+    );
+    assert_eq!(
+        map.infer("명동돈가스 본점"),
+        Some((CategoryType::PORK_CUTLET_UDON, SUFFIX_CONFIDENCE))
+    );
+    // a closer match wins over a longer keyword
+    assert_eq!(
+        map.infer("쌀국수하우스 초밥"),
+        Some((CategoryType::SUSHI, WORD_CONFIDENCE))
+    );
+    assert_eq!(
+        map.infer("하이디라오 훠궈"),
+        Some((CategoryType::CHINESE, WORD_CONFIDENCE))
+    );
+    assert_eq!(
+        map.infer("김밥천국"),
+        Some((CategoryType::LUNCH_BOX, CONTAINS_CONFIDENCE))
+    );
+    assert_eq!(map.infer("스타벅스 합정점"), None);
+}
+
+#[test]
+fn test_unmapped_report() {
+    let mut report = UnmappedReport::default();
+    for name in ["a", "b", "c", "d"] {
+        report.record("뷔페", name);
+    }
+    report.record("커피사피엔스", "e");
+    let labels = report.labels();
+    assert_eq!(labels[0].0, "뷔페");
+    assert_eq!(labels[0].1.count, 4);
+    assert_eq!(labels[0].1.examples, vec!["a", "b", "c"]);
+    assert_eq!(report.len(), 2);
+}
@@ -1,8 +1,14 @@
-use serde::Serialize;
+mod category_map;
+mod polygon;
+
+use serde::{Deserialize, Serialize};
 
 use crate::{error, utils::geo::*};
 
-#[derive(Serialize, Debug, Clone)]
+pub use category_map::{CategoryMap, UnmappedReport};
+pub use polygon::Polygon;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Coords {
     pub x: f64,
     pub y: f64,
@@ -20,10 +26,190 @@ impl Coords {
     }
 }
 
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Rect {
+    pub sw: Coords,
+    pub ne: Coords,
+}
+
+impl Rect {
+    /// Builds a rectangle from any two opposite corners.
+    pub fn from_corners(x1: f64, y1: f64, x2: f64, y2: f64) -> Result<Rect, error::Error> {
+        let sw = Coords::new(x1.min(x2), y1.min(y2))?;
+        let ne = Coords::new(x1.max(x2), y1.max(y2))?;
+        if !sw.at_south_west_from(&ne) {
+            return Err(error::Error::EmptyRect);
+        }
+        Ok(Rect { sw, ne })
+    }
+
+    /// East-west extent in meters, measured along the middle latitude.
+    pub fn width(&self) -> f64 {
+        let lat = ((self.sw.y + self.ne.y) / 2.0).to_radians();
+        (self.ne.x - self.sw.x) * METERS_PER_DEGREE * lat.cos()
+    }
+
+    /// North-south extent in meters.
+    pub fn height(&self) -> f64 {
+        (self.ne.y - self.sw.y) * METERS_PER_DEGREE
+    }
+
+    pub fn longer_side(&self) -> f64 {
+        self.width().max(self.height())
+    }
+
+    /// Whether the point lies inside or on the border.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (self.sw.x..=self.ne.x).contains(&x) && (self.sw.y..=self.ne.y).contains(&y)
+    }
+
+    /// Halves the rectangle across its longer side.
+    pub fn split(&self) -> (Rect, Rect) {
+        let (sw, ne) = (&self.sw, &self.ne);
+        if self.width() > self.height() {
+            let mid = (sw.x + ne.x) / 2.0;
+            (
+                Rect {
+                    sw: sw.clone(),
+                    ne: Coords { x: mid, y: ne.y },
+                },
+                Rect {
+                    sw: Coords { x: mid, y: sw.y },
+                    ne: ne.clone(),
+                },
+            )
+        } else {
+            let mid = (sw.y + ne.y) / 2.0;
+            (
+                Rect {
+                    sw: sw.clone(),
+                    ne: Coords { x: ne.x, y: mid },
+                },
+                Rect {
+                    sw: Coords { x: sw.x, y: mid },
+                    ne: ne.clone(),
+                },
+            )
+        }
+    }
+}
+
+/// The circle of `radius` meters around `center`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Circle {
+    pub center: Coords,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(center: Coords, radius: f64) -> Result<Circle, error::Error> {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(error::Error::InvalidRadius);
+        }
+        Ok(Circle { center, radius })
+    }
+
+    /// The smallest rectangle holding the whole circle.
+    pub fn bounds(&self) -> Result<Rect, error::Error> {
+        let Coords { x, y } = self.center;
+        let angle = self.radius / EARTH_RADIUS;
+        let d_lat = angle.to_degrees();
+        let d_long = match (angle.sin() / y.to_radians().cos()).asin() {
+            d if d.is_nan() => 180.0,
+            d => d.to_degrees(),
+        };
+        Rect::from_corners(
+            (x - d_long).max(-180.0),
+            (y - d_lat).max(-90.0),
+            (x + d_long).min(180.0),
+            (y + d_lat).min(90.0),
+        )
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        haversine(self.center.x, self.center.y, x, y) <= self.radius
+    }
+
+    /// Whether any part of `rect` lies inside the circle.
+    pub fn intersects(&self, rect: &Rect) -> bool {
+        let x = self.center.x.clamp(rect.sw.x, rect.ne.x);
+        let y = self.center.y.clamp(rect.sw.y, rect.ne.y);
+        self.contains(x, y)
+    }
+}
+
+/// The region a crawl covers. Cells are cut from its bounding rectangle, and
+/// cells and places outside of the region itself are dropped.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Area {
+    Rect(Rect),
+    Circle(Circle),
+    Polygon(Polygon),
+}
+
+impl Area {
+    pub fn bounds(&self) -> Result<Rect, error::Error> {
+        match self {
+            Self::Rect(r) => Ok(r.clone()),
+            Self::Circle(c) => c.bounds(),
+            Self::Polygon(p) => p.bounds(),
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        match self {
+            Self::Rect(r) => r.contains(x, y),
+            Self::Circle(c) => c.contains(x, y),
+            Self::Polygon(p) => p.contains(x, y),
+        }
+    }
+
+    pub fn intersects(&self, rect: &Rect) -> bool {
+        match self {
+            Self::Rect(r) => {
+                r.sw.x <= rect.ne.x
+                    && rect.sw.x <= r.ne.x
+                    && r.sw.y <= rect.ne.y
+                    && rect.sw.y <= r.ne.y
+            }
+            Self::Circle(c) => c.intersects(rect),
+            Self::Polygon(p) => p.intersects(rect),
+        }
+    }
+}
+
+impl std::fmt::Display for Area {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rect(r) => write!(f, "({}, {}), ({}, {})", r.sw.x, r.sw.y, r.ne.x, r.ne.y),
+            Self::Circle(c) => write!(f, "{}m around ({}, {})", c.radius, c.center.x, c.center.y),
+            Self::Polygon(p) => match p.bounds() {
+                Ok(r) => write!(
+                    f,
+                    "polygon within ({}, {}), ({}, {})",
+                    r.sw.x, r.sw.y, r.ne.x, r.ne.y
+                ),
+                Err(..) => write!(f, "polygon"),
+            },
+        }
+    }
+}
+
+/// Languages category names are displayed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Ko,
+    En,
+}
+
+/// An enum of unit variants stored by name, each with a Korean and an
+/// English display name. Parses from and serializes to the name.
 macro_rules! named_enum {
     (
         pub enum $name:ident {
-            $($variant:ident),*,
+            $($variant:ident($ko:literal, $en:literal)),*,
         }
     ) => {
         #[allow(clippy::upper_case_acronyms)]
@@ -34,31 +220,107 @@ macro_rules! named_enum {
         }
 
         impl $name {
+            /// Every variant, in declaration order.
+            pub const ALL: &'static [$name] = &[$($name::$variant),*];
+
             pub fn name(&self) -> &'static str {
                 match self {
                     $($name::$variant => stringify!($variant)),*
                 }
             }
+
+            /// The variant whose `name()` is `name`.
+            pub fn from_name(name: &str) -> Option<$name> {
+                match name {
+                    $(stringify!($variant) => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn label(&self, language: Language) -> &'static str {
+                match (self, language) {
+                    $(($name::$variant, Language::Ko) => $ko,
+                    ($name::$variant, Language::En) => $en,)*
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = error::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match $name::from_name(&s.trim().to_uppercase()) {
+                    Some(v) => Ok(v),
+                    None => Err(error::Error::InvalidArgument(format!(
+                        "unknown {} {s}",
+                        stringify!($name)
+                    ))),
+                }
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.name())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let name = String::deserialize(deserializer)?;
+                name.parse().map_err(serde::de::Error::custom)
+            }
         }
     };
 }
 
 named_enum! {
     pub enum CategoryType {
-        OTHERS,
-        UNDEFINED,
-        KOREAN, SEA_FOOD, MEAT, NODDLE, RAW_FISH, PORRIDGE, KOREAN_STEW,
-        JAPANESE, TUNA_SASHIMI, SUSHI, PORK_CUTLET_UDON, RAMEN, SHABU_SHABU,
-        ALCOHOL, INDOOR_STALLS, HOF_PUB, WINE_BAR, IZAKAYA, COCKTAIL_BAR,
-        DRIVERS,
-        ASIAN, SOUTH_EAST_ASIAN, INDIAN,
-        CHINESE, LAMB_SKEWERS,
-        LUNCH_BOX, FAST_FOOD, SANDWICH,
-        CAFE_DESSERT, BAKERY, RICE_CAKE, ICE_CREAM, DONUT, TOAST,
-        CHICKEN,
-        SCHOOL_FOOD,
-        WESTERN_FOOD, ITALY, PIZZA, BURGER, STEAK_RIB, MEXICAN,
-        SALAD,
+        OTHERS("기타", "Others"),
+        UNDEFINED("미분류", "Undefined"),
+        KOREAN("한식", "Korean"),
+        SEA_FOOD("해물,생선", "Seafood"),
+        MEAT("육류,고기", "Meat"),
+        NODDLE("국수", "Noodles"),
+        RAW_FISH("회", "Raw fish"),
+        PORRIDGE("죽", "Porridge"),
+        KOREAN_STEW("찌개,전골", "Stew and hot pot"),
+        JAPANESE("일식", "Japanese"),
+        TUNA_SASHIMI("참치회", "Tuna sashimi"),
+        SUSHI("초밥,롤", "Sushi and rolls"),
+        PORK_CUTLET_UDON("돈까스,우동", "Pork cutlet and udon"),
+        RAMEN("일본식라면", "Ramen"),
+        SHABU_SHABU("샤브샤브", "Shabu-shabu"),
+        ALCOHOL("술집", "Bars"),
+        INDOOR_STALLS("실내포장마차", "Indoor food stalls"),
+        HOF_PUB("호프,요리주점", "Pubs"),
+        WINE_BAR("와인바", "Wine bars"),
+        IZAKAYA("일본식주점", "Izakaya"),
+        COCKTAIL_BAR("칵테일바", "Cocktail bars"),
+        DRIVERS("기사식당", "Drivers' restaurants"),
+        ASIAN("아시아음식", "Asian"),
+        SOUTH_EAST_ASIAN("동남아음식", "Southeast Asian"),
+        INDIAN("인도음식", "Indian"),
+        CHINESE("중식", "Chinese"),
+        LAMB_SKEWERS("양꼬치", "Lamb skewers"),
+        LUNCH_BOX("도시락", "Lunch boxes"),
+        FAST_FOOD("패스트푸드", "Fast food"),
+        SANDWICH("샌드위치", "Sandwiches"),
+        CAFE_DESSERT("카페,디저트", "Cafes and desserts"),
+        BAKERY("제과,베이커리", "Bakeries"),
+        RICE_CAKE("떡,한과", "Rice cakes"),
+        ICE_CREAM("아이스크림", "Ice cream"),
+        DONUT("도넛", "Donuts"),
+        TOAST("토스트", "Toast"),
+        CHICKEN("치킨", "Fried chicken"),
+        SCHOOL_FOOD("분식", "Snack food"),
+        WESTERN_FOOD("양식", "Western"),
+        ITALY("이탈리안", "Italian"),
+        PIZZA("피자", "Pizza"),
+        BURGER("햄버거", "Burgers"),
+        STEAK_RIB("스테이크,립", "Steak and ribs"),
+        MEXICAN("멕시칸,브라질", "Mexican and Brazilian"),
+        SALAD("샐러드", "Salad"),
     }
 }
 
@@ -111,23 +373,214 @@ static CATEGORY_MAP: &[(& str, CategoryType)] = &[
     ("퓨전요리", CategoryType::OTHERS),
 ];
 
-impl From<&str> for CategoryType {
-    fn from(value: &str) -> Self {
-        match CATEGORY_MAP.iter().find(|e| e.0 == value) {
-            Some((.., t)) => t.clone(),
-            None => CategoryType::UNDEFINED,
+/// Words in place names that give away what a place serves, for places
+/// whose Kakao category path maps to nothing more specific than `OTHERS`.
+static NAME_KEYWORDS: &[(&str, CategoryType)] = &[
+    ("마라탕", CategoryType::CHINESE),
+    ("마라샹궈", CategoryType::CHINESE),
+    ("짬뽕", CategoryType::CHINESE),
+    ("짜장", CategoryType::CHINESE),
+    ("양꼬치", CategoryType::LAMB_SKEWERS),
+    ("돈까스", CategoryType::PORK_CUTLET_UDON),
+    ("돈가스", CategoryType::PORK_CUTLET_UDON),
+    ("우동", CategoryType::PORK_CUTLET_UDON),
+    ("라멘", CategoryType::RAMEN),
+    ("스시", CategoryType::SUSHI),
+    ("초밥", CategoryType::SUSHI),
+    ("샤브샤브", CategoryType::SHABU_SHABU),
+    ("이자카야", CategoryType::IZAKAYA),
+    ("쌀국수", CategoryType::SOUTH_EAST_ASIAN),
+    ("피자", CategoryType::PIZZA),
+    ("파스타", CategoryType::ITALY),
+    ("버거", CategoryType::BURGER),
+    ("스테이크", CategoryType::STEAK_RIB),
+    ("타코", CategoryType::MEXICAN),
+    ("샐러드", CategoryType::SALAD),
+    ("치킨", CategoryType::CHICKEN),
+    ("떡볶이", CategoryType::SCHOOL_FOOD),
+    ("김밥", CategoryType::SCHOOL_FOOD),
+    ("국밥", CategoryType::KOREAN),
+    ("삼겹살", CategoryType::MEAT),
+    ("갈비", CategoryType::MEAT),
+    ("곱창", CategoryType::MEAT),
+    ("냉면", CategoryType::NODDLE),
+    ("칼국수", CategoryType::NODDLE),
+    ("횟집", CategoryType::RAW_FISH),
+    ("포차", CategoryType::INDOOR_STALLS),
+    ("호프", CategoryType::HOF_PUB),
+    ("와인바", CategoryType::WINE_BAR),
+    ("도시락", CategoryType::LUNCH_BOX),
+    ("샌드위치", CategoryType::SANDWICH),
+    ("토스트", CategoryType::TOAST),
+    ("베이커리", CategoryType::BAKERY),
+    ("도넛", CategoryType::DONUT),
+    ("아이스크림", CategoryType::ICE_CREAM),
+];
+
+/// Parent of every `CategoryType` below the top level, following the Kakao
+/// category paths, e.g. "음식점 > 한식 > 해물,생선 > 회".
+static CATEGORY_PARENTS: &[(CategoryType, CategoryType)] = &[
+    (CategoryType::SEA_FOOD, CategoryType::KOREAN),
+    (CategoryType::MEAT, CategoryType::KOREAN),
+    (CategoryType::NODDLE, CategoryType::KOREAN),
+    (CategoryType::RAW_FISH, CategoryType::SEA_FOOD),
+    (CategoryType::PORRIDGE, CategoryType::KOREAN),
+    (CategoryType::KOREAN_STEW, CategoryType::KOREAN),
+    (CategoryType::TUNA_SASHIMI, CategoryType::JAPANESE),
+    (CategoryType::SUSHI, CategoryType::JAPANESE),
+    (CategoryType::PORK_CUTLET_UDON, CategoryType::JAPANESE),
+    (CategoryType::RAMEN, CategoryType::JAPANESE),
+    (CategoryType::SHABU_SHABU, CategoryType::JAPANESE),
+    (CategoryType::INDOOR_STALLS, CategoryType::ALCOHOL),
+    (CategoryType::HOF_PUB, CategoryType::ALCOHOL),
+    (CategoryType::WINE_BAR, CategoryType::ALCOHOL),
+    (CategoryType::IZAKAYA, CategoryType::ALCOHOL),
+    (CategoryType::COCKTAIL_BAR, CategoryType::ALCOHOL),
+    (CategoryType::SOUTH_EAST_ASIAN, CategoryType::ASIAN),
+    (CategoryType::INDIAN, CategoryType::ASIAN),
+    (CategoryType::LAMB_SKEWERS, CategoryType::CHINESE),
+    (CategoryType::BAKERY, CategoryType::CAFE_DESSERT),
+    (CategoryType::RICE_CAKE, CategoryType::CAFE_DESSERT),
+    (CategoryType::ICE_CREAM, CategoryType::CAFE_DESSERT),
+    (CategoryType::DONUT, CategoryType::CAFE_DESSERT),
+    (CategoryType::TOAST, CategoryType::CAFE_DESSERT),
+    (CategoryType::ITALY, CategoryType::WESTERN_FOOD),
+    (CategoryType::PIZZA, CategoryType::WESTERN_FOOD),
+    (CategoryType::BURGER, CategoryType::WESTERN_FOOD),
+    (CategoryType::STEAK_RIB, CategoryType::WESTERN_FOOD),
+    (CategoryType::MEXICAN, CategoryType::WESTERN_FOOD),
+];
+
+impl CategoryType {
+    pub fn parent(&self) -> Option<CategoryType> {
+        CATEGORY_PARENTS
+            .iter()
+            .find(|(child, ..)| child == self)
+            .map(|(.., parent)| parent.clone())
+    }
+
+    /// Parent first, up to the top level.
+    pub fn ancestors(&self) -> Vec<CategoryType> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent();
+        while let Some(c) = current {
+            current = c.parent();
+            ancestors.push(c);
         }
+        ancestors
     }
+
+    /// 0 for top level categories.
+    pub fn depth(&self) -> usize {
+        self.ancestors().len()
+    }
+}
+
+#[test]
+pub fn test_rect_from_corners() {
+    let rect = Rect::from_corners(126.93, 37.57, 126.91, 37.58).unwrap();
+    assert_eq!((rect.sw.x, rect.sw.y), (126.91, 37.57));
+    assert_eq!((rect.ne.x, rect.ne.y), (126.93, 37.58));
+    assert!(Rect::from_corners(126.91, 37.57, 126.91, 37.58).is_err());
+    assert!(Rect::from_corners(126.91, 97.57, 126.93, 37.58).is_err());
+}
+
+#[test]
+pub fn test_rect_split_along_longer_side() {
+    // about 2.6km wide and 1.1km high at this latitude
+    let rect = Rect::from_corners(126.91, 37.57, 126.94, 37.58).unwrap();
+    assert!(rect.width() > rect.height());
+    let (west, east) = rect.split();
+    assert_eq!((west.ne.x, west.ne.y), (126.925, 37.58));
+    assert_eq!((east.sw.x, east.sw.y), (126.925, 37.57));
+
+    // 0.01 degree of longitude is shorter than 0.01 degree of latitude
+    let rect = Rect::from_corners(126.91, 37.57, 126.92, 37.58).unwrap();
+    let (south, north) = rect.split();
+    assert_eq!(south.ne.y, 37.575);
+    assert_eq!(north.sw.y, 37.575);
+    assert_eq!((south.sw.x, south.ne.x), (126.91, 126.92));
 }
 
 #[test]
 pub fn test_static_map() {
-    let c = CategoryType::from("떡,한과");
+    let map = CategoryMap::default();
+    let c = map.map("떡,한과");
     assert_eq!(c, CategoryType::RICE_CAKE);
+    let c = map.map("찌개,전골");
+    assert_eq!(c, CategoryType::KOREAN_STEW);
+}
+
+#[test]
+pub fn test_category_taxonomy() {
+    assert_eq!(
+        CategoryType::PIZZA.parent(),
+        Some(CategoryType::WESTERN_FOOD)
+    );
+    assert_eq!(CategoryType::WESTERN_FOOD.parent(), None);
+    assert_eq!(
+        CategoryType::RAW_FISH.ancestors(),
+        vec![CategoryType::SEA_FOOD, CategoryType::KOREAN]
+    );
+    assert_eq!(CategoryType::JAPANESE.depth(), 0);
+    assert_eq!(CategoryType::SUSHI.depth(), 1);
+    assert_eq!(CategoryType::RAW_FISH.depth(), 2);
+    // every parent is a mapped Kakao label, so paths always reach it
+    for (.., parent) in CATEGORY_PARENTS.iter() {
+        assert!(CATEGORY_MAP.iter().any(|(.., t)| t == parent));
+    }
 }
 
 #[test]
 pub fn test_named_enum() {
     let name = CategoryType::ALCOHOL.name();
     assert_eq!(name, "ALCOHOL");
+    assert_eq!(
+        CategoryType::from_name("WINE_BAR"),
+        Some(CategoryType::WINE_BAR)
+    );
+    assert_eq!(CategoryType::from_name("wine_bar"), None);
+
+    assert_eq!(
+        "wine_bar".parse::<CategoryType>().unwrap(),
+        CategoryType::WINE_BAR
+    );
+    assert!("WINEBAR".parse::<CategoryType>().is_err());
+    assert_eq!(CategoryType::ALL.len(), 45);
+    assert!(CategoryType::ALL
+        .iter()
+        .all(|c| c.name().parse::<CategoryType>().ok().as_ref() == Some(c)));
+    assert_eq!(CategoryType::PIZZA.label(Language::Ko), "피자");
+    assert_eq!(CategoryType::SCHOOL_FOOD.label(Language::En), "Snack food");
+
+    let json = serde_json::to_string(&[CategoryType::SUSHI, CategoryType::RAMEN]).unwrap();
+    assert_eq!(json, r#"["SUSHI","RAMEN"]"#);
+    let parsed: Vec<CategoryType> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, vec![CategoryType::SUSHI, CategoryType::RAMEN]);
+    assert!(serde_json::from_str::<CategoryType>(r#""BUFFET""#).is_err());
+}
+
+#[test]
+fn test_circle() {
+    let circle = Circle::new(Coords::new(126.9237, 37.5563).unwrap(), 500.0).unwrap();
+    let bounds = circle.bounds().unwrap();
+    assert!((bounds.width() - 1000.0).abs() < 5.0);
+    assert!((bounds.height() - 1000.0).abs() < 5.0);
+    // the bounds touch the circle at the north and east ends
+    assert!(circle.contains(circle.center.x, bounds.ne.y - 1e-9));
+    assert!(circle.contains(bounds.ne.x - 1e-9, circle.center.y));
+    // but the corners lie outside
+    assert!(!circle.contains(bounds.ne.x, bounds.ne.y));
+
+    let corner = Rect::from_corners(
+        bounds.ne.x - 0.0005,
+        bounds.ne.y - 0.0005,
+        bounds.ne.x,
+        bounds.ne.y,
+    )
+    .unwrap();
+    assert!(!circle.intersects(&corner));
+    let (west, ..) = bounds.split();
+    assert!(circle.intersects(&west));
+    assert!(Circle::new(circle.center.clone(), 0.0).is_err());
 }
@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Coords, Rect};
+use crate::error;
+
+/// One or more polygons read from GeoJSON, each made of an outer ring
+/// followed by its holes. Rings are closed, the last point equals the first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Polygon {
+    polygons: Vec<Vec<Vec<Coords>>>,
+}
+
+impl Polygon {
+    pub fn from_file(path: &Path) -> Result<Polygon, error::Error> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(error::Error::FileIoFailed(e)),
+        };
+        Self::from_geojson(&text)
+    }
+
+    /// Reads a Polygon or MultiPolygon geometry, or a Feature or
+    /// FeatureCollection of them, in which case all polygons are merged.
+    pub fn from_geojson(text: &str) -> Result<Polygon, error::Error> {
+        let value: Value = match serde_json::from_str(text) {
+            Ok(v) => v,
+            Err(e) => return Err(invalid(e.to_string())),
+        };
+        let mut polygons = Vec::new();
+        collect(&value, &mut polygons)?;
+        if polygons.is_empty() {
+            return Err(invalid("no polygon found".to_string()));
+        }
+        Ok(Polygon { polygons })
+    }
+
+    pub fn bounds(&self) -> Result<Rect, error::Error> {
+        let points = self.polygons.iter().flat_map(|p| p[0].iter());
+        let (mut x1, mut y1, mut x2, mut y2) = (180.0_f64, 90.0_f64, -180.0_f64, -90.0_f64);
+        for c in points {
+            (x1, y1) = (x1.min(c.x), y1.min(c.y));
+            (x2, y2) = (x2.max(c.x), y2.max(c.y));
+        }
+        Rect::from_corners(x1, y1, x2, y2)
+    }
+
+    /// Whether the point lies inside an outer ring and outside of its holes.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.polygons.iter().any(|rings| {
+            in_ring(&rings[0], x, y) && !rings[1..].iter().any(|hole| in_ring(hole, x, y))
+        })
+    }
+
+    /// Whether any part of `rect` may lie inside the polygon. Rectangles
+    /// touching a hole's border count as intersecting.
+    pub fn intersects(&self, rect: &Rect) -> bool {
+        let (sw, ne) = (&rect.sw, &rect.ne);
+        let corners = [(sw.x, sw.y), (ne.x, sw.y), (ne.x, ne.y), (sw.x, ne.y)];
+        if corners.iter().any(|(x, y)| self.contains(*x, *y)) {
+            return true;
+        }
+        let rings = || self.polygons.iter().flatten();
+        if rings().flatten().any(|c| rect.contains(c.x, c.y)) {
+            return true;
+        }
+        let sides: Vec<_> = (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect();
+        rings().any(|ring| {
+            ring.windows(2).any(|w| {
+                let edge = ((w[0].x, w[0].y), (w[1].x, w[1].y));
+                sides.iter().any(|side| crosses(edge, *side))
+            })
+        })
+    }
+}
+
+type Point = (f64, f64);
+
+fn invalid(msg: String) -> error::Error {
+    error::Error::InvalidGeoJson(msg)
+}
+
+fn collect(value: &Value, polygons: &mut Vec<Vec<Vec<Coords>>>) -> Result<(), error::Error> {
+    let kind = value
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let coordinates = value.get("coordinates");
+    match (kind, coordinates) {
+        ("FeatureCollection", ..) => {
+            let features = match value.get("features").and_then(Value::as_array) {
+                Some(f) => f,
+                None => return Err(invalid("FeatureCollection without features".to_string())),
+            };
+            for f in features.iter() {
+                collect(f, polygons)?;
+            }
+            Ok(())
+        }
+        ("Feature", ..) => match value.get("geometry") {
+            Some(geometry) => collect(geometry, polygons),
+            None => Err(invalid("Feature without geometry".to_string())),
+        },
+        ("Polygon", Some(c)) => {
+            polygons.push(polygon(c)?);
+            Ok(())
+        }
+        ("MultiPolygon", Some(Value::Array(c))) => {
+            for p in c.iter() {
+                polygons.push(polygon(p)?);
+            }
+            Ok(())
+        }
+        (kind, ..) => Err(invalid(format!("unsupported geometry {kind:?}"))),
+    }
+}
+
+fn polygon(value: &Value) -> Result<Vec<Vec<Coords>>, error::Error> {
+    let rings = match value.as_array() {
+        Some(r) if !r.is_empty() => r,
+        _ => return Err(invalid("polygon without rings".to_string())),
+    };
+    rings.iter().map(ring).collect()
+}
+
+fn ring(value: &Value) -> Result<Vec<Coords>, error::Error> {
+    let mut ring = Vec::new();
+    for p in value.as_array().into_iter().flatten() {
+        let position: Vec<f64> = p
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_f64)
+            .collect();
+        match position[..] {
+            [x, y, ..] => ring.push(Coords::new(x, y)?),
+            _ => return Err(invalid(format!("invalid position {p}"))),
+        }
+    }
+    if let (Some(first), Some(last)) = (ring.first(), ring.last()) {
+        if (first.x, first.y) != (last.x, last.y) {
+            ring.push(first.clone());
+        }
+    }
+    if ring.len() < 4 {
+        return Err(invalid(
+            "ring with less than 3 distinct positions".to_string(),
+        ));
+    }
+    Ok(ring)
+}
+
+/// Even-odd ray casting.
+fn in_ring(ring: &[Coords], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let (a, b) = (&w[0], &w[1]);
+        if (a.y > y) != (b.y > y) && x < (b.x - a.x) * (y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Whether two segments touch or cross.
+fn crosses((p1, p2): (Point, Point), (q1, q2): (Point, Point)) -> bool {
+    let orientation = |a: Point, b: Point, c: Point| {
+        let v = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+        v.partial_cmp(&0.0).map_or(0, |o| o as i8)
+    };
+    let on_segment = |a: Point, b: Point, c: Point| {
+        c.0 >= a.0.min(b.0) && c.0 <= a.0.max(b.0) && c.1 >= a.1.min(b.1) && c.1 <= a.1.max(b.1)
+    };
+    let (d1, d2) = (orientation(q1, q2, p1), orientation(q1, q2, p2));
+    let (d3, d4) = (orientation(p1, p2, q1), orientation(p1, p2, q2));
+    if d1 * d2 < 0 && d3 * d4 < 0 {
+        return true;
+    }
+    (d1 == 0 && on_segment(q1, q2, p1))
+        || (d2 == 0 && on_segment(q1, q2, p2))
+        || (d3 == 0 && on_segment(p1, p2, q1))
+        || (d4 == 0 && on_segment(p1, p2, q2))
+}
+
+#[test]
+fn test_polygon_from_geojson() {
+    let square = Polygon::from_geojson(
+        r#"{
+            "type": "Feature",
+            "properties": { "name": "square with a hole" },
+            "geometry": {
+                "type": "Polygon",
+                "coordinates": [
+                    [[126.90, 37.55], [126.94, 37.55], [126.94, 37.59], [126.90, 37.59], [126.90, 37.55]],
+                    [[126.91, 37.56], [126.93, 37.56], [126.93, 37.58], [126.91, 37.58]]
+                ]
+            }
+        }"#,
+    )
+    .unwrap();
+    let bounds = square.bounds().unwrap();
+    assert_eq!((bounds.sw.x, bounds.ne.y), (126.90, 37.59));
+    assert!(square.contains(126.905, 37.57));
+    assert!(!square.contains(126.92, 37.57));
+    assert!(!square.contains(126.95, 37.57));
+
+    let in_hole = Rect::from_corners(126.915, 37.565, 126.925, 37.575).unwrap();
+    assert!(!square.intersects(&in_hole));
+    let across_edge = Rect::from_corners(126.93, 37.565, 126.95, 37.575).unwrap();
+    assert!(square.intersects(&across_edge));
+    let outside = Rect::from_corners(126.95, 37.55, 126.96, 37.56).unwrap();
+    assert!(!square.intersects(&outside));
+
+    let invalid = [
+        r#"{"type": "Point", "coordinates": [126.9, 37.5]}"#,
+        r#"{"type": "Polygon", "coordinates": [[[126.9, 37.5], [126.9, 37.6]]]}"#,
+        r#"{"type": "Polygon", "coordinates": [[[126.9, 97.5], [127.0, 37.6], [127.0, 37.5]]]}"#,
+        r#"{"type": "FeatureCollection", "features": []}"#,
+    ];
+    for text in invalid {
+        assert!(Polygon::from_geojson(text).is_err(), "{text}");
+    }
+}
+
+#[test]
+fn test_multi_polygon() {
+    let two = Polygon::from_geojson(
+        r#"{
+            "type": "FeatureCollection",
+            "features": [{
+                "type": "Feature",
+                "properties": {},
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [
+                        [[[126.90, 37.55], [126.91, 37.55], [126.91, 37.56], [126.90, 37.55]]],
+                        [[[126.95, 37.55], [126.96, 37.55], [126.96, 37.56], [126.95, 37.55]]]
+                    ]
+                }
+            }]
+        }"#,
+    )
+    .unwrap();
+    assert!(two.contains(126.909, 37.551));
+    assert!(two.contains(126.959, 37.551));
+    assert!(!two.contains(126.93, 37.551));
+    let between = Rect::from_corners(126.92, 37.55, 126.94, 37.56).unwrap();
+    assert!(!two.intersects(&between));
+}
@@ -1,31 +1,52 @@
 pub enum Const {
+    ConfigFile,
     KakaoRestApiKey,
     KakaoRestApiUrl,
+    KakaoKeywordApiUrl,
     DbUrl,
+    DbBatchSize,
+    DbTransactionSize,
+    CrawlGroups,
+    CrawlKeywords,
+    CrawlRequestsPerSecond,
+    CrawlDailyLimit,
+    CrawlConcurrency,
+    CategoryMap,
 }
 
 impl Const {
-    pub fn value(&self) -> String {
-        let prefix = self.get_env_value("API_DATA_SYNC");
+    /// The environment variable, treating an empty value as unset.
+    pub fn optional_value(&self) -> Option<String> {
+        std::env::var(self.key()).ok().filter(|v| !v.is_empty())
+    }
+
+    pub fn key(&self) -> String {
+        let prefix = std::env::var("API_DATA_SYNC").unwrap_or("API_DATA_SYNC".to_string());
         let key = match self {
+            Self::ConfigFile => "CONFIG",
             Self::KakaoRestApiKey => "KAKAO_REST_API_KEY",
             Self::KakaoRestApiUrl => "KAKAO_REST_API_URL",
+            Self::KakaoKeywordApiUrl => "KAKAO_KEYWORD_API_URL",
             Self::DbUrl => "EGOMOGO_DATABASE_URL",
+            Self::DbBatchSize => "DB_BATCH_SIZE",
+            Self::DbTransactionSize => "DB_TRANSACTION_SIZE",
+            Self::CrawlGroups => "CRAWL_GROUPS",
+            Self::CrawlKeywords => "CRAWL_KEYWORDS",
+            Self::CrawlRequestsPerSecond => "CRAWL_REQUESTS_PER_SECOND",
+            Self::CrawlDailyLimit => "CRAWL_DAILY_LIMIT",
+            Self::CrawlConcurrency => "CRAWL_CONCURRENCY",
+            Self::CategoryMap => "CATEGORY_MAP",
         };
-        self.get_env_value(format!("{prefix}_{key}").as_str())
-    }
-
-    fn get_env_value(&self, key: &str) -> String {
-        match std::env::var(key) {
-            Ok(v) => v,
-            Err(e) => panic!("{key} {e:?}"),
-        }
+        format!("{prefix}_{key}")
     }
 }
 
 pub mod geo {
     use crate::error;
 
+    /// Mean earth radius in meters.
+    pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
     pub fn is_lat(v: f64) -> bool {
         (-90.0..=90.0).contains(&v)
     }
@@ -44,4 +65,26 @@ pub mod geo {
         }
         Ok(())
     }
+
+    /// Great-circle distance in meters between two (longitude, latitude) points.
+    pub fn haversine(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+        let (lat1, lat2) = (y1.to_radians(), y2.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_long = (x2 - x1).to_radians();
+        let a =
+            (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_long / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    #[test]
+    fn test_haversine() {
+        assert_eq!(haversine(126.92, 37.55, 126.92, 37.55), 0.0);
+        // one degree of latitude
+        let d = haversine(126.92, 37.0, 126.92, 38.0);
+        assert!((d - EARTH_RADIUS.to_radians()).abs() < 1e-6);
+        // Seoul City Hall to Gangnam Station
+        let d = haversine(126.9780, 37.5665, 127.0276, 37.4979);
+        assert!((8_750.0..8_850.0).contains(&d), "{d}");
+        assert_eq!(d, haversine(127.0276, 37.4979, 126.9780, 37.5665));
+    }
 }